@@ -0,0 +1,358 @@
+// *********************************************************************************************************************
+// Benchmarks for a large world
+//
+// 10,000 robots obey 100 commands each (1,000,000 commands in all) in a 50x50 world.  The robots arrive in waves of
+// 1,000, and each wave is cleared away before the next arrives.  Every instruction line is preceded by a snapshot of
+// the world, just as the session takes one for :undo before every change
+//
+// The same workloads are then run on a bare grid of locations twice: once with the compact Location the world uses
+// today, and once with the previous layout kept below, so that both can be compared on the same machine in the same run
+//
+// Run with: cargo bench
+// *********************************************************************************************************************
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use rusty_robots::heading::Heading;
+use rusty_robots::location::Location;
+use rusty_robots::robot::{place_robot, Robot};
+use rusty_robots::world::{World, WORLD_MAX_HEIGHT, WORLD_MAX_WIDTH};
+
+const ROBOTS          : usize = 10_000;
+const WAVE            : usize = 1_000;
+const COMMANDS        : usize = 100;
+const SNAPSHOT_ROUNDS : usize = 10_000;
+
+// *********************************************************************************************************************
+fn main() {
+  println!("World");
+  report("Simulation", run_simulation(), ROBOTS * COMMANDS, "commands");
+  report("World snapshots", take_snapshots(), SNAPSHOT_ROUNDS, "snapshots");
+  report("Robot lookups", locate_robots(), ROBOTS, "lookups");
+
+  compare_layout::<PreviousLocation>("Previous layout");
+  compare_layout::<Location>("Compact layout");
+}
+
+fn compare_layout<L : Layout>(name : &str) {
+  println!("\n{} ({} bytes per location)", name, std::mem::size_of::<L>());
+  report("Simulation", simulate_on_grid::<L>(), ROBOTS * COMMANDS, "commands");
+  report("Grid snapshots", snapshot_grid::<L>(), SNAPSHOT_ROUNDS, "snapshots");
+  report("Robot lookups", locate_on_grid::<L>(), ROBOTS, "lookups");
+}
+
+// *********************************************************************************************************************
+// Workloads
+// *********************************************************************************************************************
+fn run_simulation() -> Duration {
+  let mut rng   = Lcg(42);
+  let     start = Instant::now();
+
+  for wave in 0..ROBOTS / WAVE {
+    let mut world  = World::new(&WORLD_MAX_WIDTH, &WORLD_MAX_HEIGHT);
+    let mut robots = vec!();
+
+    for n in 0..WAVE {
+      let id = (wave * WAVE + n) as i32;
+
+      // Keep trying until we hit a free location
+      let robot = loop {
+        let x = rng.below(WORLD_MAX_WIDTH as u32) as i32;
+        let y = rng.below(WORLD_MAX_HEIGHT as u32) as i32;
+
+        if let Ok(robot) = place_robot(Robot::new(id, x, y, Heading::North), &mut world) {
+          break robot
+        }
+      };
+
+      robots.push(robot);
+    }
+
+    for robot in &mut robots {
+      let line : String = (0..COMMANDS).map(|_| ['F', 'F', 'L', 'R'][rng.below(4) as usize]).collect();
+
+      black_box(world.clone());
+      black_box(robot.turn_and_move(&line, &mut world));
+    }
+  }
+
+  start.elapsed()
+}
+
+fn take_snapshots() -> Duration {
+  let world = populated_world();
+  let start = Instant::now();
+
+  for _ in 0..SNAPSHOT_ROUNDS {
+    black_box(world.clone());
+  }
+
+  start.elapsed()
+}
+
+fn locate_robots() -> Duration {
+  let world = populated_world();
+  let start = Instant::now();
+
+  for id in 0..ROBOTS as i32 {
+    black_box(world.locate(&(id % (WAVE as i32))));
+  }
+
+  start.elapsed()
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// The same workloads on a bare grid, for either layout
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+fn simulate_on_grid<L : Layout>() -> Duration {
+  let mut rng   = Lcg(42);
+  let     start = Instant::now();
+
+  for wave in 0..ROBOTS / WAVE {
+    let mut grid   = Grid::<L>::new();
+    let mut robots = vec!();
+
+    for n in 0..WAVE {
+      let id = (wave * WAVE + n) as i32;
+
+      robots.push(loop {
+        let x = rng.below(WORLD_MAX_WIDTH as u32) as i32;
+        let y = rng.below(WORLD_MAX_HEIGHT as u32) as i32;
+
+        if grid.at(x, y).occupant().is_none() {
+          grid.at_mut(x, y).occupy(id);
+          break (id, x, y, Heading::North, false)
+        }
+      });
+    }
+
+    for robot in &mut robots {
+      let line : Vec<char> = (0..COMMANDS).map(|_| ['F', 'F', 'L', 'R'][rng.below(4) as usize]).collect();
+
+      black_box(grid.clone());
+      grid.obey(robot, &line);
+    }
+  }
+
+  start.elapsed()
+}
+
+fn snapshot_grid<L : Layout>() -> Duration {
+  let grid  = populated_grid::<L>();
+  let start = Instant::now();
+
+  for _ in 0..SNAPSHOT_ROUNDS {
+    black_box(grid.clone());
+  }
+
+  start.elapsed()
+}
+
+fn locate_on_grid<L : Layout>() -> Duration {
+  let grid  = populated_grid::<L>();
+  let start = Instant::now();
+
+  for id in 0..ROBOTS as i32 {
+    black_box(grid.locations.iter().position(|loc| loc.occupant() == Some(id % (WAVE as i32))));
+  }
+
+  start.elapsed()
+}
+
+// *********************************************************************************************************************
+// Location layouts
+// *********************************************************************************************************************
+trait Layout : Clone {
+  fn vacant(x : i32, y : i32) -> Self;
+  fn occupant(&self) -> Option<i32>;
+  fn occupy(&mut self, id : i32);
+  fn vacate(&mut self);
+  fn is_scented(&self, heading : &Heading) -> bool;
+  fn add_scent(&mut self, heading : &Heading);
+}
+
+impl Layout for Location {
+  fn vacant(_x : i32, _y : i32) -> Self            { Location::new() }
+  fn occupant(&self) -> Option<i32>                 { Location::occupant(self) }
+  fn occupy(&mut self, id : i32)                    { Location::occupy(self, id) }
+  fn vacate(&mut self)                              { Location::vacate(self) }
+  fn is_scented(&self, heading : &Heading) -> bool  { Location::is_scented(self, heading) }
+  fn add_scent(&mut self, heading : &Heading)       { Location::add_scent(self, heading) }
+}
+
+// How a location was stored before it was made compact.  Some fields are never read here, but they still have to be
+// allocated and copied
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+struct PreviousLocation {
+  id : i32
+, x  : i32
+, y  : i32
+
+, can_go_north : bool
+, can_go_south : bool
+, can_go_east  : bool
+, can_go_west  : bool
+
+, charger : Option<u32>
+, items   : Vec<String>
+}
+
+impl Layout for PreviousLocation {
+  fn vacant(x : i32, y : i32) -> Self {
+    PreviousLocation {
+      id           : -1
+    , x
+    , y
+    , can_go_north : true
+    , can_go_south : true
+    , can_go_east  : true
+    , can_go_west  : true
+    , charger      : None
+    , items        : vec!()
+    }
+  }
+
+  fn occupant(&self) -> Option<i32> {
+    if self.id == -1 { None } else { Some(self.id) }
+  }
+
+  fn occupy(&mut self, id : i32) {
+    self.id = id;
+  }
+
+  fn vacate(&mut self) {
+    self.id = -1;
+  }
+
+  fn is_scented(&self, heading : &Heading) -> bool {
+    !match heading {
+      Heading::North => self.can_go_north
+    , Heading::East  => self.can_go_east
+    , Heading::South => self.can_go_south
+    , Heading::West  => self.can_go_west
+    }
+  }
+
+  fn add_scent(&mut self, heading : &Heading) {
+    match heading {
+      Heading::North => self.can_go_north = false
+    , Heading::East  => self.can_go_east  = false
+    , Heading::South => self.can_go_south = false
+    , Heading::West  => self.can_go_west  = false
+    }
+  }
+}
+
+// Just enough of a world to move robots about on
+#[derive(Clone)]
+struct Grid<L : Layout> {
+  locations : Vec<L>
+}
+
+impl<L : Layout> Grid<L> {
+  fn new() -> Grid<L> {
+    Grid {
+      locations : (0..WORLD_MAX_WIDTH * WORLD_MAX_HEIGHT).map(|idx| L::vacant(idx % WORLD_MAX_WIDTH, idx / WORLD_MAX_WIDTH)).collect()
+    }
+  }
+
+  fn at(&self, x : i32, y : i32) -> &L {
+    &self.locations[(y * WORLD_MAX_WIDTH + x) as usize]
+  }
+
+  fn at_mut(&mut self, x : i32, y : i32) -> &mut L {
+    &mut self.locations[(y * WORLD_MAX_WIDTH + x) as usize]
+  }
+
+  // Turn and move a robot (id, x, y, heading, is_lost) just as the world does, scents included
+  fn obey(&mut self, robot : &mut (i32, i32, i32, Heading, bool), cmds : &[char]) {
+    let (id, x, y, heading, is_lost) = robot;
+
+    for cmd in cmds {
+      match cmd {
+        'L' => *heading = heading.left()
+      , 'R' => *heading = heading.right()
+      , _   => {
+          if self.at(*x, *y).is_scented(heading) {
+            continue
+          }
+
+          let (dx, dy)       = heading.offset();
+          let (new_x, new_y) = (*x + dx, *y + dy);
+
+          if !(0..WORLD_MAX_WIDTH).contains(&new_x) || !(0..WORLD_MAX_HEIGHT).contains(&new_y) {
+            self.at_mut(*x, *y).vacate();
+            self.at_mut(*x, *y).add_scent(heading);
+            *is_lost = true;
+            return
+          }
+
+          if self.at(new_x, new_y).occupant().is_none() {
+            self.at_mut(*x, *y).vacate();
+            self.at_mut(new_x, new_y).occupy(*id);
+            *x = new_x;
+            *y = new_y;
+          }
+        }
+      }
+    }
+  }
+}
+
+// *********************************************************************************************************************
+// Helpers
+// *********************************************************************************************************************
+
+// A world with a wave of robots standing around in it
+fn populated_world() -> World {
+  let mut rng   = Lcg(7);
+  let mut world = World::new(&WORLD_MAX_WIDTH, &WORLD_MAX_HEIGHT);
+  let mut id    = 0;
+
+  while id < WAVE as i32 {
+    let x = rng.below(WORLD_MAX_WIDTH as u32) as i32;
+    let y = rng.below(WORLD_MAX_HEIGHT as u32) as i32;
+
+    if place_robot(Robot::new(id, x, y, Heading::North), &mut world).is_ok() {
+      id += 1;
+    }
+  }
+
+  world
+}
+
+// The same robots standing around on a bare grid
+fn populated_grid<L : Layout>() -> Grid<L> {
+  let mut rng  = Lcg(7);
+  let mut grid = Grid::<L>::new();
+  let mut id   = 0;
+
+  while id < WAVE as i32 {
+    let x = rng.below(WORLD_MAX_WIDTH as u32) as i32;
+    let y = rng.below(WORLD_MAX_HEIGHT as u32) as i32;
+
+    if grid.at(x, y).occupant().is_none() {
+      grid.at_mut(x, y).occupy(id);
+      id += 1;
+    }
+  }
+
+  grid
+}
+
+fn report(name : &str, elapsed : Duration, count : usize, what : &str) {
+  println!( "{:<19}: {:>9.3} ms ({:.0} {} per second)"
+          , name, elapsed.as_secs_f64() * 1000.0, count as f64 / elapsed.as_secs_f64(), what
+          );
+}
+
+// Numerical Recipes' linear congruential generator: good enough to scatter robots about, and the same every run
+struct Lcg(u64);
+
+impl Lcg {
+  fn below(&mut self, n : u32) -> u32 {
+    self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+    ((self.0 >> 33) % n as u64) as u32
+  }
+}
@@ -0,0 +1,306 @@
+use std::{fs, io, panic, thread};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::input::read_file;
+use crate::rules::Rules;
+use crate::scenario::Scenario;
+use crate::session::Session;
+use crate::validate::Validation;
+use crate::world::{Dialect, Limits};
+use crate::trace::Trace;
+
+const LIB_NAME     : &str  = module_path!();
+const TRACE_ACTIVE : &bool = &false;
+
+const RESULT_PASS  : &str = "PASS";
+const RESULT_FAIL  : &str = "FAIL";
+const RESULT_ERROR : &str = "ERROR";
+
+// *********************************************************************************************************************
+// How a scenario file fared
+// *********************************************************************************************************************
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+  Passed                // Every line was accepted and every goal met
+, GoalsMissed           // Every line was accepted, but some goal was not met
+, Errors(usize)         // This many lines were rejected, either before the file was run or while it was running
+, Unreadable(String)    // The file (or a file it includes) could not be read at all
+, Panicked(String)      // Running the file crashed, with this message
+}
+
+// *********************************************************************************************************************
+// The result of running one scenario file in a session of its own
+// *********************************************************************************************************************
+#[derive(Debug)]
+pub struct FileRun {
+  pub path       : PathBuf
+, pub transcript : Vec<String>    // Everything the REPL would have printed, warnings and errors included
+, pub outcome    : Outcome
+, pub elapsed    : Duration
+}
+
+// *********************************************************************************************************************
+// Run a single scenario file
+//
+// The whole file is parsed first, and only run if no problems are found in it
+// *********************************************************************************************************************
+pub fn run_file(path : &Path, validation : Validation, dialect : Dialect, limits : Limits, rules : Rules) -> FileRun {
+  let start = Instant::now();
+
+  let lines = match read_file(path) {
+    Ok(lines) => lines
+  , Err(err)  => return FileRun {
+      path       : path.to_path_buf()
+    , transcript : vec!()
+    , outcome    : Outcome::Unreadable(err.to_string())
+    , elapsed    : start.elapsed()
+    }
+  };
+
+  let scenario = match Scenario::parse(&lines, dialect, validation, limits) {
+    Ok(scenario) => scenario
+  , Err(errs)    => return FileRun {
+      path       : path.to_path_buf()
+    , transcript : errs.iter().map(|err| format!("Error at {}", err)).collect()
+    , outcome    : Outcome::Errors(errs.len())
+    , elapsed    : start.elapsed()
+    }
+  };
+
+  let mut session    = Session::new(validation).with_dialect(dialect).with_limits(limits).with_rules(rules);
+  let mut transcript = vec!();
+  let mut errors     = 0;
+
+  for statement in &scenario.statements {
+    match session.handle_statement(&statement.node) {
+      Ok(reply) => {
+        transcript.extend(reply.warnings.iter().map(|w| format!("Warning: {}", w)));
+        transcript.extend(reply.output);
+      }
+    , Err(err) => {
+        errors += 1;
+        transcript.push(format!("Error at {}: {}", statement.span, err));
+      }
+    }
+  }
+
+  transcript.extend(session.final_report());
+
+  let outcome = match (errors, session.goals_met()) {
+    (0, true)  => Outcome::Passed
+  , (0, false) => Outcome::GoalsMissed
+  , (n, _)     => Outcome::Errors(n)
+  };
+
+  FileRun { path : path.to_path_buf(), transcript, outcome, elapsed : start.elapsed() }
+}
+
+// *********************************************************************************************************************
+// Run many scenario files at once
+//
+// Each worker thread takes the next file nobody has started on yet and runs it in a session of its own, so a thread
+// that finishes early doesn't sit idle while the others still have work.  Nothing is shared between threads other than
+// the list of paths and the position of the next one.  A file that crashes is reported as such, without stopping the
+// others.  The results come back in the same order as the paths
+// *********************************************************************************************************************
+pub fn run_files( paths : &[PathBuf], threads : usize, validation : Validation, dialect : Dialect, limits : Limits, rules : Rules
+                ) -> Vec<FileRun> {
+  run_all(paths, threads, |path| run_file(path, validation, dialect, limits, rules))
+}
+
+fn run_all<F>(paths : &[PathBuf], threads : usize, run : F) -> Vec<FileRun>
+where F : Fn(&Path) -> FileRun + Sync {
+  const FN_NAME : &str = "run_files";
+
+  let trace_boundary = Trace::make_boundary_trace_fn(TRACE_ACTIVE, LIB_NAME, FN_NAME);
+  let trace          = Trace::make_trace_fn(TRACE_ACTIVE, LIB_NAME, FN_NAME);
+
+  trace_boundary(&Some(true));
+
+  let threads = threads.clamp(1, paths.len().max(1));
+  trace(&format!("Running {} file(s) on {} thread(s)", paths.len(), threads));
+
+  // The position in the list of the next file to be run
+  let next = AtomicUsize::new(0);
+
+  let mut runs : Vec<(usize, FileRun)> = thread::scope(|scope| {
+    let workers : Vec<_> = (0..threads)
+      .map(|_| scope.spawn(|| {
+        let mut runs = vec!();
+
+        loop {
+          let idx = next.fetch_add(1, Ordering::Relaxed);
+
+          match paths.get(idx) {
+            Some(path) => runs.push((idx, run_caught(path, &run)))
+          , None       => break runs
+          }
+        }
+      }))
+      .collect();
+
+    // Every run is caught, so a worker can't panic
+    workers.into_iter().flat_map(|worker| worker.join().unwrap_or_default()).collect()
+  });
+
+  runs.sort_by_key(|(idx, _)| *idx);
+
+  trace_boundary(&Some(false));
+  runs.into_iter().map(|(_, run)| run).collect()
+}
+
+// *********************************************************************************************************************
+// Turn a mixture of file and directory names into a list of files
+//
+// The files in a directory are taken in name order, but subdirectories are not searched
+// *********************************************************************************************************************
+pub fn collect_files(args : &[String]) -> io::Result<Vec<PathBuf>> {
+  let mut paths = vec!();
+
+  for arg in args {
+    let path = PathBuf::from(arg);
+
+    if path.is_dir() {
+      let mut files : Vec<PathBuf> = fs::read_dir(&path)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<io::Result<Vec<PathBuf>>>()?
+        .into_iter()
+        .filter(|p| p.is_file())
+        .collect();
+
+      files.sort();
+      paths.extend(files);
+    }
+    else {
+      paths.push(path);
+    }
+  }
+
+  Ok(paths)
+}
+
+// *********************************************************************************************************************
+// One line per file, then the totals
+// *********************************************************************************************************************
+pub fn summary(runs : &[FileRun], elapsed : Duration) -> Vec<String> {
+  let width = runs.iter().map(|run| run.path.display().to_string().len()).max().unwrap_or(0);
+
+  let mut report : Vec<String> = runs.iter()
+    .map(|run| {
+      let (result, detail) = match &run.outcome {
+        Outcome::Passed          => (RESULT_PASS,  String::new())
+      , Outcome::GoalsMissed     => (RESULT_FAIL,  String::from("goals not met"))
+      , Outcome::Errors(n)       => (RESULT_ERROR, format!("{} line(s) rejected", n))
+      , Outcome::Unreadable(err) => (RESULT_ERROR, err.clone())
+      , Outcome::Panicked(msg)   => (RESULT_ERROR, format!("crashed: {}", msg))
+      };
+
+      format!("{:<width$}  {:<5}  {:>9.3} ms  {}", run.path.display(), result, millis(run.elapsed), detail, width = width)
+        .trim_end()
+        .to_string()
+    })
+    .collect();
+
+  let passed = runs.iter().filter(|run| run.outcome == Outcome::Passed).count();
+
+  report.push(format!( "{} file(s): {} passed, {} failed in {:.3} ms"
+                     , runs.len(), passed, runs.len() - passed, millis(elapsed)
+                     ));
+  report
+}
+
+// *********************************************************************************************************************
+// Private API
+// *********************************************************************************************************************
+// Run one file, turning a crash into an outcome of its own
+fn run_caught<F>(path : &Path, run : &F) -> FileRun
+where F : Fn(&Path) -> FileRun {
+  let start = Instant::now();
+
+  panic::catch_unwind(panic::AssertUnwindSafe(|| run(path))).unwrap_or_else(|payload| {
+    let message = payload.downcast_ref::<&str>().map(|s| s.to_string())
+      .or_else(|| payload.downcast_ref::<String>().cloned())
+      .unwrap_or_else(|| String::from("unknown panic"));
+
+    FileRun { path : path.to_path_buf(), transcript : vec!(), outcome : Outcome::Panicked(message), elapsed : start.elapsed() }
+  })
+}
+
+fn millis(duration : Duration) -> f64 {
+  duration.as_secs_f64() * 1000.0
+}
+
+// *********************************************************************************************************************
+// Suppose we'd better test it...
+// *********************************************************************************************************************
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::robot::Robot;
+  use crate::test_dir::TestDir;
+  use crate::world::World;
+
+  fn assert_send<T : Send>() {}
+
+  #[test]
+  fn worlds_and_robots_can_cross_threads() {
+    assert_send::<World>();
+    assert_send::<Robot>();
+    assert_send::<FileRun>();
+  }
+
+  #[test]
+  fn run_scenarios_in_parallel() {
+    let dir = TestDir::new("batch");
+
+    // Fragments kept in a subdirectory are included, but not run on their own
+    dir.write("common/world.txt", "5 3  # the classic sample\n");
+    dir.write("a_classic.txt", "include common/world.txt\n1 1 E\nRFRFRFRF\n\n# now for a lost one\n3 2 N\nFRRFLLFFRRFLL\n");
+    dir.write("b_goals.txt",   "5 3\n:goal reach 4 0\n0 0 E\nFF\n");
+    dir.write("c_errors.txt",  "5 3\n9 9 N\n:bogus\n");
+
+    let mut paths = collect_files(&[dir.path().display().to_string()]).unwrap();
+    paths.push(dir.join("d_missing.txt"));
+
+    let runs = run_files(&paths, 3, Validation::Strict, Dialect::Native, Limits::DEFAULT, Rules::default());
+
+    assert_eq!(runs.iter().map(|run| run.path.file_name().unwrap().to_str().unwrap()).collect::<Vec<&str>>()
+              , vec!("a_classic.txt", "b_goals.txt", "c_errors.txt", "d_missing.txt")
+              );
+    assert_eq!(runs[0].transcript, vec!("1 1 E", "3 2 N LOST"));
+    assert_eq!(runs[0].outcome,    Outcome::Passed);
+    assert_eq!(runs[1].outcome,    Outcome::GoalsMissed);
+    assert_eq!(runs[2].outcome,    Outcome::Errors(2));
+    assert!(matches!(runs[3].outcome, Outcome::Unreadable(_)));
+
+    // The same file read as the classic problem, where "5 3" is the upper-right location
+    let classic = run_file(&paths[0], Validation::Strict, Dialect::Classic, Limits::DEFAULT, Rules::default());
+    assert_eq!(classic.transcript, vec!("1 1 E", "3 3 N LOST"));
+
+    let report = summary(&runs, Duration::from_millis(5));
+    assert!(report[0].contains("a_classic.txt") && report[0].contains(RESULT_PASS));
+    assert!(report[2].ends_with("2 line(s) rejected"));
+    assert!(runs[2].transcript[0].starts_with(&format!("Error at {}:2:1: ", dir.join("c_errors.txt").display())));
+    assert!(report[4].starts_with("4 file(s): 1 passed, 3 failed in 5.000 ms"));
+  }
+
+  #[test]
+  fn a_crash_is_only_one_result() {
+    let paths : Vec<PathBuf> = ["one.txt", "boom.txt", "three.txt"].iter().map(PathBuf::from).collect();
+
+    let runs = run_all(&paths, 2, |path| {
+      if path.ends_with("boom.txt") {
+        panic!("attempt to add with overflow")
+      }
+
+      FileRun { path : path.to_path_buf(), transcript : vec!(), outcome : Outcome::Passed, elapsed : Duration::ZERO }
+    });
+
+    assert_eq!( runs.iter().map(|run| &run.outcome).collect::<Vec<&Outcome>>()
+              , vec!(&Outcome::Passed, &Outcome::Panicked(String::from("attempt to add with overflow")), &Outcome::Passed)
+              );
+    assert!(summary(&runs, Duration::ZERO)[1].ends_with("crashed: attempt to add with overflow"));
+  }
+}
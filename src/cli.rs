@@ -0,0 +1,545 @@
+use std::{fmt, io, str};
+use std::error::Error;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use serde_json::{json, Value};
+
+use crate::{batch, repl, rest, server, verify};
+use crate::input::{read_file, read_lines, SourceLine};
+use crate::rules::Rules;
+use crate::scenario::Scenario;
+use crate::session::Session;
+use crate::trace::{Trace, TraceLevel};
+use crate::validate::Validation;
+use crate::world::{Dialect, Limits};
+
+const LIB_NAME     : &str  = module_path!();
+const TRACE_ACTIVE : &bool = &false;
+
+// The name given to standard in when saying where a line came from
+const STDIN_NAME : &str = "<stdin>";
+
+pub const PARSE_ERROR_BAD_FORMAT : &str = "Output format must be either text or json";
+
+pub const USAGE : &str = "Usage: rusty_robots [options] [repl | run [file] | render [file] | verify <file|dir>... | batch <file|dir>... | serve [port]]";
+
+pub const HELP_TEXT : &str = "\
+Usage: rusty_robots [options] [command]
+
+Commands:
+  repl                       Read commands from standard in, interactively when it is a terminal (the default)
+  run [file]                 Run a scenario file (or standard in) and print everything it says
+  render [file]              Run a scenario file (or standard in) quietly, then print the world it leaves behind
+  verify <file|dir>...       Check declarative TOML scenarios against their expected robot reports
+  batch <file|dir>...        Run many scenario files in parallel and summarise the results
+  serve [port]               Serve the line protocol over TCP (port 7878), or JSON over HTTP (port 8080) with --http
+
+Options:
+  -i, --input <file>         The scenario file for run or render
+  -f, --format text|json     How run, render and verify print their results (default text)
+      --dialect native|classic
+                             How the size of new worlds is given (default native)
+      --max-width <n>        The widest world that may be created, up to 1000 (default 50)
+      --max-height <n>       The highest world that may be created, up to 1000 (default 50)
+      --trace off|calls|all  Trace function calls, or calls and data, in every module to stderr (default off)
+      --collisions block|halt
+                             After bumping into something, carry on with the instructions or stop (default block)
+      --scents on|off        Whether lost robots leave a scent that warns others away (default on)
+      --lenient              Obey the valid commands in an instruction line instead of rejecting the whole line
+      --port <port>          The port for serve
+      --http                 Serve JSON over HTTP instead of the line protocol
+  -h, --help                 Print this help";
+
+// *********************************************************************************************************************
+// What to do
+// *********************************************************************************************************************
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Command {
+  Repl
+, Run
+, Render
+, Verify
+, Batch
+, Serve
+, Help
+}
+
+// *********************************************************************************************************************
+// How results are printed
+// *********************************************************************************************************************
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum Format {
+  #[default]
+  Text
+, Json
+}
+
+impl fmt::Display for Format {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Format::Text => write!(f, "text")
+    , Format::Json => write!(f, "json")
+    }
+  }
+}
+
+impl str::FromStr for Format {
+  type Err = &'static str;
+
+  fn from_str(s: &str) -> Result<Format, Self::Err> {
+    match s.trim().to_ascii_lowercase().as_str() {
+      "text" => Ok(Format::Text)
+    , "json" => Ok(Format::Json)
+    , _      => Err(PARSE_ERROR_BAD_FORMAT)
+    }
+  }
+}
+
+// *********************************************************************************************************************
+// Everything given on the command line
+// *********************************************************************************************************************
+#[derive(Debug, PartialEq)]
+pub struct Options {
+  pub command    : Command
+, pub input      : Option<PathBuf>    // For run and render.  Standard in is read if there is none
+, pub files      : Vec<String>        // Files and directories for verify and batch
+, pub format     : Format
+, pub dialect    : Dialect
+, pub validation : Validation
+, pub limits     : Limits
+, pub trace      : TraceLevel
+, pub rules      : Rules
+, pub port       : Option<u16>        // The default depends on whether --http is given
+, pub http       : bool
+}
+
+impl Default for Options {
+  fn default() -> Options {
+    Options {
+      command    : Command::Repl
+    , input      : None
+    , files      : vec!()
+    , format     : Format::Text
+    , dialect    : Dialect::Native
+    , validation : Validation::Strict
+    , limits     : Limits::DEFAULT
+    , trace      : TraceLevel::Off
+    , rules      : Rules::default()
+    , port       : None
+    , http       : false
+    }
+  }
+}
+
+// *********************************************************************************************************************
+// Options implementation
+// *********************************************************************************************************************
+impl Options {
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  // Options may come before or after the command, and take their value either as the next argument or after an '='
+  pub fn parse(args : &[String]) -> Result<Options, String> {
+    let mut options    = Options::default();
+    let mut positional = vec!();
+    let mut max_width  = None;
+    let mut max_height = None;
+    let mut args       = args.iter();
+
+    while let Some(arg) = args.next() {
+      if !arg.starts_with('-') || arg == "-" {
+        positional.push(arg.clone());
+        continue
+      }
+
+      let (flag, inline) = match arg.split_once('=') {
+        Some((flag, value)) => (flag, Some(value.to_string()))
+      , None                => (arg.as_str(), None)
+      };
+
+      // Flags that take no value
+      match flag {
+        "-h" | "--help" => { options.command = Command::Help; return Ok(options) }
+      , "--lenient"     => { options.validation = Validation::Lenient; continue }
+      , "--http"        => { options.http = true; continue }
+      , _               => ()
+      }
+
+      // Flags that take a value, which is only looked for once the flag is known
+      let mut inline = inline;
+      let mut value  = || inline.take().or_else(|| args.next().cloned()).ok_or_else(|| format!("{} needs a value", flag));
+      let bad        = |err : &dyn fmt::Display| format!("{}: {}", flag, err);
+
+      match flag {
+        "-i" | "--input"  => options.input            = Some(PathBuf::from(value()?))
+      , "-f" | "--format" => options.format           = value()?.parse().map_err(|e| bad(&e))?
+      , "--dialect"       => options.dialect          = value()?.parse().map_err(|e| bad(&e))?
+      , "--trace"         => options.trace            = value()?.parse().map_err(|e| bad(&e))?
+      , "--collisions"    => options.rules.collisions = value()?.parse().map_err(|e| bad(&e))?
+      , "--scents"        => options.rules.scents     = value()?.parse().map_err(|e| bad(&e))?
+      , "--max-width"     => max_width                = Some(value()?.parse::<i32>().map_err(|e| bad(&e))?)
+      , "--max-height"    => max_height               = Some(value()?.parse::<i32>().map_err(|e| bad(&e))?)
+      , "--port"          => options.port             = Some(value()?.parse::<u16>().map_err(|e| bad(&e))?)
+      , _                 => return Err(format!("Unknown option {}", flag))
+      }
+    }
+
+    options.limits = Limits::new( max_width.unwrap_or(Limits::DEFAULT.max_width)
+                                , max_height.unwrap_or(Limits::DEFAULT.max_height)
+                                ).map_err(String::from)?;
+
+    let mut positional = positional.into_iter();
+
+    options.command = match positional.next().as_deref() {
+      None | Some("repl") => Command::Repl
+    , Some("run")         => Command::Run
+    , Some("render")      => Command::Render
+    , Some("verify")      => Command::Verify
+    , Some("batch")       => Command::Batch
+    , Some("serve")       => Command::Serve
+    , Some("help")        => Command::Help
+    , Some(other)         => return Err(format!("Unknown command {}", other))
+    };
+
+    let rest : Vec<String> = positional.collect();
+
+    match options.command {
+      Command::Run | Command::Render => match (rest.as_slice(), &options.input) {
+        ([], _)        => ()
+      , ([file], None) => options.input = Some(PathBuf::from(file))
+      , _              => return Err(String::from("Expecting one scenario file at most"))
+      }
+    , Command::Verify | Command::Batch if rest.is_empty() => return Err(String::from("Expecting at least one file or directory"))
+    , Command::Verify | Command::Batch                    => options.files = rest
+    , Command::Serve => match rest.as_slice() {
+        []     => ()
+      , [port] => options.port = Some(port.parse::<u16>().map_err(|e| format!("{}: {}", port, e))?)
+      , _      => return Err(String::from("Expecting one port number at most"))
+      }
+    , _ if !rest.is_empty() => return Err(format!("Unexpected argument {}", rest[0]))
+    , _                     => ()
+    }
+
+    if options.input.is_some() && !matches!(options.command, Command::Run | Command::Render) {
+      return Err(String::from("--input is only for run and render"))
+    }
+
+    if options.format == Format::Json && !matches!(options.command, Command::Run | Command::Render | Command::Verify) {
+      return Err(String::from("--format json is only for run, render and verify"))
+    }
+
+    Ok(options)
+  }
+
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  // Put the trace level in force for the rest of the run
+  pub fn install(&self) {
+    Trace::set_level(self.trace);
+  }
+
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  // Carry out the command
+  pub fn execute(&self) -> Result<(), Box<dyn Error>> {
+    const FN_NAME : &str = "execute";
+
+    let trace_boundary = Trace::make_boundary_trace_fn(TRACE_ACTIVE, LIB_NAME, FN_NAME);
+
+    trace_boundary(&Some(true));
+
+    let result = match self.command {
+      Command::Repl   => repl::run(self.validation, self.dialect, self.limits, self.rules).map_err(|e| e.into())
+    , Command::Run    => self.run()
+    , Command::Render => self.render()
+    , Command::Verify => self.verify()
+    , Command::Batch  => self.batch()
+    , Command::Serve if self.http => rest::serve(self.port.unwrap_or(rest::DEFAULT_HTTP_PORT), self.validation, self.limits, self.rules).map_err(|e| e.into())
+    , Command::Serve  => server::serve(self.port.unwrap_or(server::DEFAULT_PORT), self.validation, self.limits, self.rules).map_err(|e| e.into())
+    , Command::Help   => { println!("{}", HELP_TEXT); Ok(()) }
+    };
+
+    trace_boundary(&Some(false));
+    result
+  }
+
+  // *******************************************************************************************************************
+  // Private API
+  // *******************************************************************************************************************
+
+  // Print everything the scenario says, just as the REPL would
+  fn run(&self) -> Result<(), Box<dyn Error>> {
+    let outcome = self.play()?;
+
+    match self.format {
+      Format::Json => println!("{}", outcome.to_json())
+    , Format::Text => {
+        for line in outcome.transcript() {
+          println!("{}", line);
+        }
+      }
+    }
+
+    if outcome.passed() { Ok(()) } else { Err("The scenario did not pass".into()) }
+  }
+
+  // Print only the world the scenario leaves behind, and the robots in it
+  fn render(&self) -> Result<(), Box<dyn Error>> {
+    let outcome = self.play()?;
+
+    match self.format {
+      Format::Json => println!("{}", outcome.world_json())
+    , Format::Text => {
+        for line in outcome.errors() {
+          eprintln!("{}", line);
+        }
+
+        match outcome.session.world() {
+          Some(world) => {
+            print!("{}", world);
+            outcome.session.robots().iter().for_each(|robot| println!("{}", robot.report(world)));
+          }
+        , None => return Err("The scenario never created a world".into())
+        }
+      }
+    }
+
+    if outcome.errors().is_empty() { Ok(()) } else { Err("The scenario did not run cleanly".into()) }
+  }
+
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  // Check every declarative scenario named on the command line (or found in a named directory) against its expected
+  // robot reports
+  fn verify(&self) -> Result<(), Box<dyn Error>> {
+    let paths : Vec<_> = batch::collect_files(&self.files)?
+      .into_iter()
+      .filter(|path| path.extension().is_some_and(|ext| ext == verify::SCENARIO_EXTENSION))
+      .collect();
+
+    if paths.is_empty() {
+      return Err(format!("No .{} files found", verify::SCENARIO_EXTENSION).into())
+    }
+
+    let verifications : Vec<_> = paths.iter().map(|path| verify::verify_file(path, self.validation, self.limits, self.rules)).collect();
+
+    match self.format {
+      Format::Json => println!("{}", serde_json::to_string_pretty(&verifications)?)
+    , Format::Text => verify::report(&verifications).iter().for_each(|line| println!("{}", line))
+    }
+
+    if verifications.iter().all(verify::Verification::passed) {
+      Ok(())
+    }
+    else {
+      Err("Some scenarios did not verify".into())
+    }
+  }
+
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  // Run every scenario file named on the command line (or found in a named directory) and summarise the results
+  fn batch(&self) -> Result<(), Box<dyn Error>> {
+    let paths = batch::collect_files(&self.files)?;
+
+    if paths.is_empty() {
+      return Err("No scenario files found".into())
+    }
+
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let start   = Instant::now();
+    let runs    = batch::run_files(&paths, threads, self.validation, self.dialect, self.limits, self.rules);
+
+    for line in batch::summary(&runs, start.elapsed()) {
+      println!("{}", line);
+    }
+
+    if runs.iter().all(|run| run.outcome == batch::Outcome::Passed) {
+      Ok(())
+    }
+    else {
+      Err("Some scenarios did not pass".into())
+    }
+  }
+
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  // Read the input, then parse it and run it in a session of its own
+  fn play(&self) -> Result<Outcome, Box<dyn Error>> {
+    let lines = match &self.input {
+      Some(path) => read_file(path)
+    , None       => {
+        let mut text = String::new();
+        io::stdin().read_to_string(&mut text)?;
+        read_lines(&text, Path::new(STDIN_NAME))
+      }
+    }.map_err(|err| err.to_string())?;
+
+    Ok(Outcome::play(&lines, self.dialect, self.validation, self.limits, self.rules))
+  }
+}
+
+// *********************************************************************************************************************
+// What became of each statement of a scenario
+// *********************************************************************************************************************
+#[derive(Debug)]
+struct Played {
+  at       : String           // Where the statement came from
+, input    : String
+, output   : Vec<String>
+, warnings : Vec<String>
+, error    : Option<(&'static str, String)>
+}
+
+#[derive(Debug)]
+struct Outcome {
+  session  : Session
+, rejected : Vec<String>      // Problems found before anything was run, in which case nothing was
+, played   : Vec<Played>
+}
+
+impl Outcome {
+  fn play(lines : &[SourceLine], dialect : Dialect, validation : Validation, limits : Limits, rules : Rules) -> Outcome {
+    let mut session = Session::new(validation).with_dialect(dialect).with_limits(limits).with_rules(rules);
+
+    let scenario = match Scenario::parse(lines, dialect, validation, limits) {
+      Ok(scenario) => scenario
+    , Err(errs)    => return Outcome { session, rejected : errs.iter().map(|err| format!("Error at {}", err)).collect(), played : vec!() }
+    };
+
+    let played = scenario.statements.iter()
+      .map(|statement| {
+        let input = statement.node.to_string();
+        let at    = statement.span.to_string();
+
+        match session.handle_statement(&statement.node) {
+          Ok(reply) => Played { at, input, output : reply.output, warnings : reply.warnings, error : None }
+        , Err(err)  => Played { at, input, output : vec!(), warnings : vec!(), error : Some((err.code(), err.message())) }
+        }
+      })
+      .collect();
+
+    Outcome { session, rejected : vec!(), played }
+  }
+
+  fn errors(&self) -> Vec<String> {
+    self.played.iter()
+      .filter_map(|p| p.error.as_ref().map(|(_, message)| format!("Error at {}: {}", p.at, message)))
+      .chain(self.rejected.iter().cloned())
+      .collect()
+  }
+
+  fn passed(&self) -> bool {
+    self.errors().is_empty() && self.session.goals_met()
+  }
+
+  // Everything the REPL would have printed, warnings and errors included
+  fn transcript(&self) -> Vec<String> {
+    let mut lines = self.rejected.clone();
+
+    for p in &self.played {
+      lines.extend(p.warnings.iter().map(|w| format!("Warning: {}", w)));
+      lines.extend(p.output.iter().cloned());
+
+      if let Some((_, message)) = &p.error {
+        lines.push(format!("Error at {}: {}", p.at, message));
+      }
+    }
+
+    lines.extend(self.session.final_report());
+    lines
+  }
+
+  fn to_json(&self) -> Value {
+    json!({
+      "rejected"   : self.rejected
+    , "statements" : self.played.iter().map(|p| json!({
+        "at"       : p.at
+      , "input"    : p.input
+      , "output"   : p.output
+      , "warnings" : p.warnings
+      , "error"    : p.error.as_ref().map(|(code, message)| json!({ "code" : code, "message" : message }))
+      })).collect::<Vec<Value>>()
+    , "report"     : self.session.final_report()
+    , "passed"     : self.passed()
+    })
+  }
+
+  fn world_json(&self) -> Value {
+    let world = self.session.world().map(|world| json!({
+      "name"      : self.session.world_name()
+    , "width"     : world.width
+    , "height"    : world.height
+    , "coords"    : world.coords.to_string()
+    , "robots"    : self.session.robots().iter().map(|robot| robot.report(world)).collect::<Vec<String>>()
+    , "rendering" : world.to_string()
+    }));
+
+    json!({ "world" : world, "errors" : self.errors() })
+  }
+}
+
+// *********************************************************************************************************************
+// Suppose we'd better test it...
+// *********************************************************************************************************************
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::rules::{Collisions, ScentMode};
+
+  fn parse(args : &str) -> Result<Options, String> {
+    Options::parse(&args.split_whitespace().map(String::from).collect::<Vec<String>>())
+  }
+
+  #[test]
+  fn parse_options() {
+    assert_eq!(parse(""), Ok(Options::default()));
+    assert_eq!(parse("run --help").map(|o| o.command), Ok(Command::Help));
+
+    let options = parse("--format=json run --trace all sample.txt --collisions halt --scents off --max-width 80").unwrap();
+    assert_eq!(options.command, Command::Run);
+    assert_eq!(options.input, Some(PathBuf::from("sample.txt")));
+    assert_eq!((options.format, options.trace), (Format::Json, TraceLevel::All));
+    assert_eq!(options.rules, Rules { collisions : Collisions::Halt, scents : ScentMode::Off });
+    assert_eq!((options.limits.max_width, options.limits.max_height), (80, 50));
+
+    let options = parse("--dialect classic --lenient serve 9000 --http").unwrap();
+    assert_eq!((options.dialect, options.validation), (Dialect::Classic, Validation::Lenient));
+    assert_eq!((options.command, options.port, options.http), (Command::Serve, Some(9000), true));
+
+    assert_eq!(parse("verify scenarios extra").unwrap().files, vec!("scenarios", "extra"));
+  }
+
+  #[test]
+  fn reject_bad_options() {
+    assert_eq!(parse("--format"), Err(String::from("--format needs a value")));
+    assert_eq!(parse("--format xml run"), Err(format!("--format: {}", PARSE_ERROR_BAD_FORMAT)));
+    assert_eq!(parse("--colour"), Err(String::from("Unknown option --colour")));
+    assert_eq!(parse("fly"), Err(String::from("Unknown command fly")));
+    assert_eq!(parse("verify"), Err(String::from("Expecting at least one file or directory")));
+    assert_eq!(parse("-i a.txt render b.txt"), Err(String::from("Expecting one scenario file at most")));
+    assert_eq!(parse("repl -i a.txt"), Err(String::from("--input is only for run and render")));
+    assert_eq!(parse("-f json serve"), Err(String::from("--format json is only for run, render and verify")));
+    assert_eq!(parse("--max-height 0"), Err(String::from(crate::world::PARSE_ERROR_BAD_LIMITS)));
+    assert!(parse("--trace loud").unwrap_err().starts_with("--trace: "));
+  }
+
+  #[test]
+  fn play_scenario() {
+    let lines   = read_lines("5 3\n1 1 E\nRFRFRFRF\n3 2 N\nFRRFLLFFRRFLL\n", Path::new("inline")).unwrap();
+    let outcome = Outcome::play(&lines, Dialect::Native, Validation::Strict, Limits::DEFAULT, Rules::default());
+
+    assert!(outcome.passed());
+    assert_eq!(outcome.transcript(), vec!("1 1 E", "3 2 N LOST"));
+
+    let json = outcome.to_json();
+    assert_eq!(json["statements"][2]["output"], json!(["1 1 E"]));
+    assert_eq!(json["statements"][0]["at"], json!("inline:1:1"));
+
+    let world = outcome.world_json();
+    assert_eq!(world["world"]["width"], json!(5));
+    assert_eq!(world["world"]["robots"], json!(["1 1 E", "3 2 N LOST"]));
+
+    let lines   = read_lines("5 3\n1 1 E\nFQ\n", Path::new("inline")).unwrap();
+    let outcome = Outcome::play(&lines, Dialect::Native, Validation::Strict, Limits::DEFAULT, Rules::default());
+
+    assert_eq!(outcome.rejected.len(), 1);
+    assert!(!outcome.passed());
+    assert!(outcome.to_json()["statements"].as_array().unwrap().is_empty());
+  }
+}
@@ -0,0 +1,115 @@
+use std::{fmt, str};
+
+pub const PARSE_ERROR_BAD_COORDS : &str = "Coordinates must be given as a base of 0 or 1 followed by an origin of bottom-left or top-left";
+
+// *********************************************************************************************************************
+// Which corner of the world is (0,0) or (1,1)?
+// *********************************************************************************************************************
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum Origin {
+  #[default]
+  BottomLeft    // Y increases going north
+, TopLeft       // Y increases going south
+}
+
+impl fmt::Display for Origin {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Origin::BottomLeft => write!(f, "bottom-left")
+    , Origin::TopLeft    => write!(f, "top-left")
+    }
+  }
+}
+
+// *********************************************************************************************************************
+// Coordinate convention definition
+//
+// Internally, locations are always zero-based with the origin at the bottom-left.  The convention only changes how
+// coordinates are read and written, so headings keep their compass meaning: north is always towards the top
+// *********************************************************************************************************************
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Coords {
+  pub base   : i32      // The number of the first row and column: 0 or 1
+, pub origin : Origin
+}
+
+impl fmt::Display for Coords {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{} {}", self.base, self.origin)
+  }
+}
+
+// *********************************************************************************************************************
+// Coordinate convention implementation
+// *********************************************************************************************************************
+impl Coords {
+  // From the world's own coordinates to the ones shown to the outside world
+  pub fn external(&self, x : i32, y : i32, height : i32) -> (i32, i32) {
+    match self.origin {
+      Origin::BottomLeft => (x + self.base, y + self.base)
+    , Origin::TopLeft    => (x + self.base, height - 1 - y + self.base)
+    }
+  }
+
+  // From the coordinates given by the outside world to the world's own
+  pub fn internal(&self, x : i32, y : i32, height : i32) -> (i32, i32) {
+    match self.origin {
+      Origin::BottomLeft => (x - self.base, y - self.base)
+    , Origin::TopLeft    => (x - self.base, height - 1 - (y - self.base))
+    }
+  }
+}
+
+impl str::FromStr for Coords {
+  type Err = &'static str;
+
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  // <base> <origin>, for example "1 top-left"
+  fn from_str(s: &str) -> Result<Coords, Self::Err> {
+    let args : Vec<String> = s.split_ascii_whitespace().map(str::to_ascii_lowercase).collect();
+
+    match args.iter().map(String::as_str).collect::<Vec<&str>>()[..] {
+      [base, origin] => {
+        let base = match base {
+          "0" => 0
+        , "1" => 1
+        , _   => return Err(PARSE_ERROR_BAD_COORDS)
+        };
+
+        let origin = match origin {
+          "bottom-left" => Origin::BottomLeft
+        , "top-left"    => Origin::TopLeft
+        , _             => return Err(PARSE_ERROR_BAD_COORDS)
+        };
+
+        Ok(Coords { base, origin })
+      }
+    , _ => Err(PARSE_ERROR_BAD_COORDS)
+    }
+  }
+}
+
+// *********************************************************************************************************************
+// Suppose we'd better test it...
+// *********************************************************************************************************************
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn conventions() {
+    let classic = Coords::default();
+    let screen  = "1 TOP-LEFT".parse::<Coords>().unwrap();
+
+    assert_eq!(screen, Coords { base : 1, origin : Origin::TopLeft });
+    assert_eq!(screen.to_string(), "1 top-left");
+    assert_eq!("2 top-left".parse::<Coords>(), Err(PARSE_ERROR_BAD_COORDS));
+    assert_eq!("1".parse::<Coords>(),          Err(PARSE_ERROR_BAD_COORDS));
+
+    // The bottom-left location of a world 3 high
+    assert_eq!(classic.external(0, 0, 3), (0, 0));
+    assert_eq!(screen.external(0, 0, 3),  (1, 3));
+    assert_eq!(screen.internal(1, 3, 3),  (0, 0));
+    assert_eq!(screen.internal(2, 1, 3),  (1, 2));
+  }
+}
@@ -0,0 +1,220 @@
+use crate::heading::Heading;
+use crate::robot::{Robot, Step};
+use crate::rules::Collisions;
+use crate::world::World;
+use crate::trace::Trace;
+
+const LIB_NAME     : &str  = module_path!();
+const TRACE_ACTIVE : &bool = &false;
+
+pub const DRY_RUN_PREFIX : char = '?';
+
+// *********************************************************************************************************************
+// A forward step that would not have moved the robot
+// *********************************************************************************************************************
+#[derive(Debug, PartialEq, Clone)]
+pub struct SkippedStep {
+  pub step       : usize                  // One-based position of the command within the instruction string
+, pub x          : i32
+, pub y          : i32
+, pub heading    : Heading
+, pub blocked_by : Option<Obstruction>    // What stood in the way, if anything did
+}
+
+// What stopped a forward step
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Obstruction {
+  Robot
+, Obstacle
+}
+
+impl Obstruction {
+  fn at(x : &i32, y : &i32, world : &World) -> Obstruction {
+    if world.is_obstacle(x, y) { Obstruction::Obstacle } else { Obstruction::Robot }
+  }
+}
+
+// *********************************************************************************************************************
+// Dry run definition
+// *********************************************************************************************************************
+#[derive(Debug)]
+pub struct DryRun {
+  pub robot   : Robot                    // The robot as it would be after obeying the instructions
+, pub lost_at : Option<SkippedStep>      // Where the robot would fall off the edge of the world
+, pub blocked : Vec<SkippedStep>         // Forward steps that would hit another robot or an obstacle
+, pub scented : Vec<SkippedStep>         // Forward steps that would be skipped because of a scent
+}
+
+// *********************************************************************************************************************
+// Dry run implementation
+// *********************************************************************************************************************
+impl DryRun {
+  pub fn is_lost(&self) -> bool {
+    self.robot.is_lost
+  }
+
+  // What would happen, with locations given in the world's coordinate convention
+  pub fn describe(&self, world : &World) -> String {
+    let at = |s : &SkippedStep| {
+      let (x, y) = world.to_external(&s.x, &s.y);
+      format!("({},{})", x, y)
+    };
+
+    let mut text = format!("Would finish at {}", self.robot.report(world));
+
+    if let Some(lost) = &self.lost_at {
+      text.push_str(&format!("\n  Step {}: lost heading {} from {}", lost.step, lost.heading, at(lost)));
+    }
+
+    for b in &self.blocked {
+      let what = match b.blocked_by {
+        Some(Obstruction::Obstacle) => "blocked by an obstacle"
+      , _                           => "already occupied"
+      };
+
+      text.push_str(&format!("\n  Step {}: blocked heading {} from {} - location {}", b.step, b.heading, at(b), what));
+    }
+
+    for s in &self.scented {
+      text.push_str(&format!("\n  Step {}: skipped heading {} from {} - here be monsters", s.step, s.heading, at(s)));
+    }
+
+    text
+  }
+}
+
+// *********************************************************************************************************************
+// Simulate a set of move/turn instructions without changing either the robot or the world
+// *********************************************************************************************************************
+pub fn dry_run(robot : &Robot, line : &str, world : &World) -> DryRun {
+  const FN_NAME : &str = "dry_run";
+
+  let trace_boundary = Trace::make_boundary_trace_fn(TRACE_ACTIVE, LIB_NAME, FN_NAME);
+  let trace          = Trace::make_trace_fn(TRACE_ACTIVE, LIB_NAME, FN_NAME);
+
+  trace_boundary(&Some(true));
+
+  // Any scents laid or locations vacated during the simulation only affect these copies
+  let mut sim_robot = robot.clone();
+  let mut sim_world = world.clone();
+
+  let mut result = DryRun {
+    robot   : robot.clone()
+  , lost_at : None
+  , blocked : vec!()
+  , scented : vec!()
+  };
+
+  let cmds = line.split_ascii_whitespace().next().unwrap_or("").to_ascii_uppercase();
+
+  for (idx, c) in cmds.chars().enumerate() {
+    if sim_robot.is_lost {
+      break;
+    }
+
+    let before = SkippedStep {
+      step       : idx + 1
+    , x          : sim_robot.x
+    , y          : sim_robot.y
+    , heading    : sim_robot.heading.clone()
+    , blocked_by : None
+    };
+
+    let step   = sim_robot.obey(c, &mut sim_world);
+    let halted = step.is_blocked() && sim_world.rules.collisions == Collisions::Halt;
+
+    match step {
+      Step::Lost          => result.lost_at = Some(before)
+    , Step::Blocked(x, y) => result.blocked.push(SkippedStep { blocked_by : Some(Obstruction::at(&x, &y, &sim_world)), ..before })
+    , Step::Stopped(x, y) => result.blocked.push(SkippedStep { x : sim_robot.x, y : sim_robot.y, blocked_by : Some(Obstruction::at(&x, &y, &sim_world)), ..before })
+    , Step::Scented       => result.scented.push(before)
+    , _                   => ()
+    }
+
+    // Just as when the instructions are obeyed for real, a collision is the end of the line under this policy
+    if halted {
+      break;
+    }
+  }
+
+  trace(&format!("Robot {} would finish at {}", robot.id, sim_robot.report(&sim_world)));
+  result.robot = sim_robot;
+
+  trace_boundary(&Some(false));
+  result
+}
+
+// *********************************************************************************************************************
+// Suppose we'd better test it...
+// *********************************************************************************************************************
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn dry_run_leaves_world_untouched() {
+    let mut world = World::new(&6, &4);
+    let     robot = Robot::new(0, 3, 2, Heading::North);
+    world.place_robot_at(&robot.id, &robot.x, &robot.y);
+
+    let result = dry_run(&robot, "FRRFLLFFRRFLL", &world);
+
+    assert!(result.is_lost());
+    assert_eq!(result.lost_at, Some(SkippedStep { step : 8, x : 3, y : 3, heading : Heading::North, blocked_by : None }));
+
+    // Neither the robot nor the world have changed
+    assert_eq!((robot.x, robot.y, robot.is_lost), (3, 2, false));
+    assert!(world.is_location_occupied(&3, &2));
+    assert!(world.is_it_safe(&3, &3, &Heading::North));
+  }
+
+  #[test]
+  fn dry_run_reports_skipped_steps() {
+    let mut world = World::new(&5, &3);
+    let     robot = Robot::new(0, 3, 2, Heading::North);
+    world.place_robot_at(&robot.id, &robot.x, &robot.y);
+    world.place_robot_at(&1, &2, &2);
+    world.here_be_monsters(&3, &2, &Heading::North);
+
+    let result = dry_run(&robot, "FLF", &world);
+
+    assert!(!result.is_lost());
+    assert_eq!(result.scented, vec!(SkippedStep { step : 1, x : 3, y : 2, heading : Heading::North, blocked_by : None }));
+    assert_eq!(result.blocked, vec!(SkippedStep { step : 3, x : 3, y : 2, heading : Heading::West, blocked_by : Some(Obstruction::Robot) }));
+    assert_eq!(result.robot.to_string(), "3 2 W");
+  }
+
+  #[test]
+  fn dry_run_halts_like_the_real_thing() {
+    let mut world = World::new(&5, &3);
+    let mut robot = Robot::new(0, 1, 1, Heading::East);
+    world.rules.collisions = Collisions::Halt;
+    world.place_robot_at(&robot.id, &robot.x, &robot.y);
+    world.place_robot_at(&1, &2, &1);
+
+    let result = dry_run(&robot, "FLF", &world);
+
+    assert_eq!(result.blocked, vec!(SkippedStep { step : 1, x : 1, y : 1, heading : Heading::East, blocked_by : Some(Obstruction::Robot) }));
+    assert_eq!(result.robot.to_string(), "1 1 E");
+
+    robot.turn_and_move("FLF", &mut world);
+    assert_eq!(robot.to_string(), result.robot.to_string());
+  }
+
+  #[test]
+  fn dry_run_says_what_is_in_the_way() {
+    let mut world = World::new(&5, &3);
+    let     robot = Robot::new(0, 1, 1, Heading::East);
+    world.place_robot_at(&robot.id, &robot.x, &robot.y);
+    world.place_robot_at(&1, &1, &2);
+    world.add_obstacle(&2, &1);
+
+    let result = dry_run(&robot, "FLF", &world);
+
+    assert_eq!( result.describe(&world)
+              , "Would finish at 1 1 N\n  \
+                 Step 1: blocked heading E from (1,1) - location blocked by an obstacle\n  \
+                 Step 3: blocked heading N from (1,1) - location already occupied"
+              );
+  }
+}
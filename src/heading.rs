@@ -1,6 +1,6 @@
 use std::{fmt, str};
 
-pub const PARSE_ERROR_INVALID_HEADING : &str = &"Invalid heading";
+pub const PARSE_ERROR_INVALID_HEADING : &str = "Invalid heading";
 
 // *********************************************************************************************************************
 // Heading definition
@@ -28,6 +28,38 @@ impl fmt::Display for Heading {
   }
 }
 
+impl Heading {
+  // Heading after a 90° turn to the left
+  pub fn left(&self) -> Heading {
+    match self {
+      Heading::North => Heading::West
+    , Heading::West  => Heading::South
+    , Heading::South => Heading::East
+    , Heading::East  => Heading::North
+    }
+  }
+
+  // Heading after a 90° turn to the right
+  pub fn right(&self) -> Heading {
+    match self {
+      Heading::North => Heading::East
+    , Heading::East  => Heading::South
+    , Heading::South => Heading::West
+    , Heading::West  => Heading::North
+    }
+  }
+
+  // Change in (x,y) when taking one step along this heading
+  pub fn offset(&self) -> (i32, i32) {
+    match self {
+      Heading::North => ( 0,  1)
+    , Heading::East  => ( 1,  0)
+    , Heading::South => ( 0, -1)
+    , Heading::West  => (-1,  0)
+    }
+  }
+}
+
 impl str::FromStr for Heading {
   type Err = &'static str;
 
@@ -76,6 +108,14 @@ mod tests {
     assert_eq!(p08.unwrap(), Heading::West);
   }  
 
+  #[test]
+  fn rotate() {
+    assert_eq!(Heading::North.left(),  Heading::West);
+    assert_eq!(Heading::North.right(), Heading::East);
+    assert_eq!(Heading::West.right().right(), Heading::East);
+    assert_eq!(Heading::South.offset(), (0, -1));
+  }
+
   #[test]
   fn parse_test_invalid() {
     let p01 = "a".parse::<Heading>();
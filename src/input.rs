@@ -0,0 +1,186 @@
+use std::{fmt, fs};
+use std::path::{Path, PathBuf};
+
+pub const COMMENT_PREFIX  : char = '#';
+pub const INCLUDE_KEYWORD : &str = "include";
+
+const MAX_INCLUDE_DEPTH : usize = 16;
+
+// *********************************************************************************************************************
+// What a line of input is for
+// *********************************************************************************************************************
+#[derive(Debug, PartialEq)]
+pub enum Line<'a> {
+  Blank                 // Nothing but whitespace
+, Comment               // Nothing but a comment
+, Include(&'a str)      // Read the lines of another file in place of this one
+, Command(&'a str)      // Anything else is for the session, with any trailing comment removed
+}
+
+// *********************************************************************************************************************
+// A line that made it through to the session, and where it came from
+// *********************************************************************************************************************
+#[derive(Debug, PartialEq)]
+pub struct SourceLine {
+  pub path   : PathBuf
+, pub number : usize      // 1-based
+, pub column : usize      // 1-based, where the text starts
+, pub text   : String
+}
+
+impl fmt::Display for SourceLine {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}:{}", self.path.display(), self.number)
+  }
+}
+
+// *********************************************************************************************************************
+// Reasons why a file could not be read
+// *********************************************************************************************************************
+#[derive(Debug, PartialEq)]
+pub enum InputError {
+  Unreadable(PathBuf, String)     // The file could not be read
+, MissingFileName(PathBuf, usize) // "include" was not followed by a file name
+, IncludeCycle(PathBuf)           // The file includes itself, directly or otherwise
+, TooDeep(PathBuf)                // Includes are nested too deeply
+}
+
+impl fmt::Display for InputError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      InputError::Unreadable(path, err)       => write!(f, "Cannot read {}: {}", path.display(), err)
+    , InputError::MissingFileName(path, line) => write!(f, "{}:{}: {} needs a file name", path.display(), line, INCLUDE_KEYWORD)
+    , InputError::IncludeCycle(path)          => write!(f, "{} includes itself", path.display())
+    , InputError::TooDeep(path)               => write!( f, "Cannot include {} - includes are nested more than {} deep"
+                                                       , path.display(), MAX_INCLUDE_DEPTH
+                                                       )
+    }
+  }
+}
+
+// *********************************************************************************************************************
+// Classify one line of input
+// *********************************************************************************************************************
+pub fn classify(line : &str) -> Line<'_> {
+  let text = match line.find(COMMENT_PREFIX) {
+    Some(idx) => &line[..idx]
+  , None      => line
+  }.trim();
+
+  if text.is_empty() {
+    return if line.trim().is_empty() { Line::Blank } else { Line::Comment }
+  }
+
+  let mut words = text.splitn(2, char::is_whitespace);
+
+  match (words.next(), words.next()) {
+    (Some(INCLUDE_KEYWORD), file) => Line::Include(file.unwrap_or("").trim())
+  , _                             => Line::Command(text)
+  }
+}
+
+// *********************************************************************************************************************
+// Read the lines of a file that are meant for the session
+//
+// Blank lines and comments are dropped, and each include is replaced by the lines of the named file.  An included file
+// is looked for relative to the directory of the file that includes it
+// *********************************************************************************************************************
+pub fn read_file(path : &Path) -> Result<Vec<SourceLine>, InputError> {
+  let mut lines = vec!();
+  read_into(path, &mut vec!(), &mut lines)?;
+  Ok(lines)
+}
+
+// The same for text that has already been read, where the path is only used to find included files and to say where
+// each line came from
+pub fn read_lines(text : &str, path : &Path) -> Result<Vec<SourceLine>, InputError> {
+  let mut lines = vec!();
+  lines_into(text, path, &mut vec!(), &mut lines)?;
+  Ok(lines)
+}
+
+// *********************************************************************************************************************
+// Private API
+// *********************************************************************************************************************
+fn read_into(path : &Path, stack : &mut Vec<PathBuf>, lines : &mut Vec<SourceLine>) -> Result<(), InputError> {
+  if stack.len() == MAX_INCLUDE_DEPTH {
+    return Err(InputError::TooDeep(path.to_path_buf()))
+  }
+
+  let contents = fs::read_to_string(path).map_err(|err| InputError::Unreadable(path.to_path_buf(), err.to_string()))?;
+  lines_into(&contents, path, stack, lines)
+}
+
+fn lines_into(contents : &str, path : &Path, stack : &mut Vec<PathBuf>, lines : &mut Vec<SourceLine>) -> Result<(), InputError> {
+  let identity = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+
+  if stack.contains(&identity) {
+    return Err(InputError::IncludeCycle(path.to_path_buf()))
+  }
+
+  stack.push(identity);
+
+  for (idx, line) in contents.lines().enumerate() {
+    match classify(line) {
+      Line::Blank | Line::Comment => ()
+    , Line::Include("")           => return Err(InputError::MissingFileName(path.to_path_buf(), idx + 1))
+    , Line::Include(file)         => {
+        let included = path.parent().map_or(PathBuf::from(file), |dir| dir.join(file));
+        read_into(&included, stack, lines)?;
+      }
+    , Line::Command(text) => lines.push(SourceLine {
+        path   : path.to_path_buf()
+      , number : idx + 1
+      , column : line.chars().take_while(|c| c.is_whitespace()).count() + 1
+      , text   : text.to_string()
+      })
+    }
+  }
+
+  stack.pop();
+  Ok(())
+}
+
+// *********************************************************************************************************************
+// Suppose we'd better test it...
+// *********************************************************************************************************************
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_dir::TestDir;
+
+  #[test]
+  fn classify_lines() {
+    assert_eq!(classify(""),                        Line::Blank);
+    assert_eq!(classify("   "),                     Line::Blank);
+    assert_eq!(classify("  # The classic sample"),  Line::Comment);
+    assert_eq!(classify("1 1 E  # first robot"),    Line::Command("1 1 E"));
+    assert_eq!(classify(":show"),                   Line::Command(":show"));
+    assert_eq!(classify("include common/world.txt"), Line::Include("common/world.txt"));
+    assert_eq!(classify("include"),                 Line::Include(""));
+    assert_eq!(classify("included"),                Line::Command("included"));
+  }
+
+  #[test]
+  fn include_files() {
+    let dir = TestDir::new("input");
+
+    dir.write("common/world.txt", "# A small world\n5 3\n\ninclude scents.txt\n");
+    dir.write("common/scents.txt", ":reset scents\n");
+    dir.write("main.txt", "include common/world.txt\n\n1 1 E # robot\nRFRFRFRF\n");
+    dir.write("loop.txt", "5 3\ninclude loop.txt\n");
+    dir.write("bare.txt", "include\n");
+
+    let lines = read_file(&dir.join("main.txt")).unwrap();
+    assert_eq!( lines.iter().map(|l| l.text.as_str()).collect::<Vec<&str>>()
+              , vec!("5 3", ":reset scents", "1 1 E", "RFRFRFRF")
+              );
+    assert_eq!(lines[1].to_string(), format!("{}:1", dir.join("common").join("scents.txt").display()));
+    assert_eq!((lines[2].number, lines[2].column), (3, 1));
+    assert_eq!(read_lines("  5 3\n", Path::new("inline")).unwrap()[0].column, 3);
+
+    assert_eq!(read_file(&dir.join("loop.txt")), Err(InputError::IncludeCycle(dir.join("loop.txt"))));
+    assert_eq!(read_file(&dir.join("bare.txt")), Err(InputError::MissingFileName(dir.join("bare.txt"), 1)));
+    assert!(matches!(read_file(&dir.join("nowhere.txt")), Err(InputError::Unreadable(..))));
+  }
+}
@@ -0,0 +1,73 @@
+use std::{fmt, str};
+
+pub const PARSE_ERROR_INVALID_KIND : &str = "Robot type must be rover, hopper, heavy or scout";
+
+// *********************************************************************************************************************
+// Kind definition
+// *********************************************************************************************************************
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum Kind {
+  #[default]
+  Rover     // Moves one location per forward step
+, Hopper    // Moves two locations per forward step
+, Heavy     // Pushes a robot in its way one location further on
+, Scout     // Pays no attention to scents
+}
+
+// *********************************************************************************************************************
+// Kind implementation
+// *********************************************************************************************************************
+impl fmt::Display for Kind {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let txt = match self {
+      Kind::Rover  => "rover"
+    , Kind::Hopper => "hopper"
+    , Kind::Heavy  => "heavy"
+    , Kind::Scout  => "scout"
+    };
+    write!(f, "{}", txt)
+  }
+}
+
+impl Kind {
+  // How many locations does a single forward step cover?
+  pub fn stride(&self) -> u32 {
+    match self {
+      Kind::Hopper => 2
+    , _            => 1
+    }
+  }
+}
+
+impl str::FromStr for Kind {
+  type Err = &'static str;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let kind = match s.to_ascii_lowercase().as_str() {
+      "rover"  => Kind::Rover
+    , "hopper" => Kind::Hopper
+    , "heavy"  => Kind::Heavy
+    , "scout"  => Kind::Scout
+    , _        => return Err(PARSE_ERROR_INVALID_KIND)
+    };
+
+    Ok(kind)
+  }
+}
+
+// *********************************************************************************************************************
+// Suppose we'd better test it...
+// *********************************************************************************************************************
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse_kinds() {
+    assert_eq!("hopper".parse::<Kind>(), Ok(Kind::Hopper));
+    assert_eq!("HEAVY".parse::<Kind>(),  Ok(Kind::Heavy));
+    assert_eq!("tank".parse::<Kind>(),   Err(PARSE_ERROR_INVALID_KIND));
+    assert_eq!(Kind::default().to_string(), "rover");
+    assert_eq!(Kind::Hopper.stride(), 2);
+  }
+}
@@ -0,0 +1,29 @@
+// *********************************************************************************************************************
+// The simulator as a library, so that benchmarks and other tools can drive worlds and robots directly
+// *********************************************************************************************************************
+pub mod trace;
+pub mod robot;
+pub mod location;
+pub mod coords;
+pub mod rules;
+pub mod mission;
+pub mod world;
+pub mod heading;
+pub mod kind;
+pub mod dry_run;
+pub mod program;
+pub mod scheduler;
+pub mod validate;
+pub mod input;
+pub mod scenario;
+pub mod session;
+pub mod repl;
+pub mod server;
+pub mod rest;
+pub mod script;
+pub mod batch;
+pub mod verify;
+pub mod cli;
+
+#[cfg(test)]
+mod test_dir;
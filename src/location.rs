@@ -1,36 +1,106 @@
+use std::num::NonZeroU32;
+
+use crate::heading::Heading;
+
 // *********************************************************************************************************************
 // Location definition
+//
+// A location only knows who (if anyone) is standing on it, whether an obstacle stands there instead, and in which
+// directions robots have been lost.  Its coordinates follow from its position in the world, and the few charging
+// stations and items are kept by the world itself, so a location fits in 8 bytes
 // *********************************************************************************************************************
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
 pub struct Location {
-  pub id : i32
-, pub x  : i32
-, pub y  : i32
-
-, pub can_go_north : bool
-, pub can_go_south : bool
-, pub can_go_east  : bool
-, pub can_go_west  : bool
+  occupant : Option<NonZeroU32>   // Robot id + 1, so that robot 0 can be told apart from nobody
+, scents   : u8                   // One bit per heading in which it is no longer safe to go
+, obstacle : bool                 // Nothing can enter this location
 }
 
 // *********************************************************************************************************************
 // Location implementation
 // *********************************************************************************************************************
 impl Location {
-  pub fn move_to(&mut self, x : i32, y : i32) {
-    self.x = x;
-    self.y = y;
-  }
-
-  pub fn new(x : i32, y : i32) -> Location {
-    Location {
-      id : -1               // Location currently unoccupied
-    , x
-    , y
-    , can_go_north : true   // Currently, it's safe to go in this direction
-    , can_go_south : true   // Currently, it's safe to go in this direction
-    , can_go_east  : true   // Currently, it's safe to go in this direction
-    , can_go_west  : true   // Currently, it's safe to go in this direction
-    }
-  }
-}
\ No newline at end of file
+  pub fn occupant(&self) -> Option<i32> {
+    self.occupant.map(|id| (id.get() - 1) as i32)
+  }
+
+  pub fn is_occupied_by(&self, robot_id : i32) -> bool {
+    self.occupant == NonZeroU32::new(robot_id as u32 + 1)
+  }
+
+  pub fn occupy(&mut self, robot_id : i32) {
+    self.occupant = NonZeroU32::new(robot_id as u32 + 1);
+  }
+
+  pub fn vacate(&mut self) {
+    self.occupant = None;
+  }
+
+  pub fn is_obstacle(&self) -> bool {
+    self.obstacle
+  }
+
+  pub fn block(&mut self) {
+    self.obstacle = true;
+  }
+
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  // Scents
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  pub fn is_scented(&self, heading : &Heading) -> bool {
+    self.scents & scent_bit(heading) != 0
+  }
+
+  pub fn add_scent(&mut self, heading : &Heading) {
+    self.scents |= scent_bit(heading);
+  }
+
+  pub fn clear_scents(&mut self) {
+    self.scents = 0;
+  }
+
+  pub fn new() -> Location {
+    Location::default()
+  }
+}
+
+// *********************************************************************************************************************
+// Private API
+// *********************************************************************************************************************
+fn scent_bit(heading : &Heading) -> u8 {
+  match heading {
+    Heading::North => 0b0001
+  , Heading::East  => 0b0010
+  , Heading::South => 0b0100
+  , Heading::West  => 0b1000
+  }
+}
+
+// *********************************************************************************************************************
+// Suppose we'd better test it...
+// *********************************************************************************************************************
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn compact_location() {
+    let mut loc = Location::new();
+
+    assert_eq!(std::mem::size_of::<Location>(), 8);
+    assert_eq!(loc.occupant(), None);
+
+    loc.occupy(0);
+    assert_eq!(loc.occupant(), Some(0));
+    loc.vacate();
+    assert_eq!(loc.occupant(), None);
+
+    loc.block();
+    assert!(loc.is_obstacle());
+
+    loc.add_scent(&Heading::West);
+    assert!(loc.is_scented(&Heading::West) && !loc.is_scented(&Heading::East));
+    loc.clear_scents();
+    assert!(!loc.is_scented(&Heading::West));
+  }
+}
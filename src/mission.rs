@@ -0,0 +1,256 @@
+use std::{fmt, str};
+
+use crate::robot::Robot;
+use crate::world::World;
+
+pub const PARSE_ERROR_MISSING_GOAL : &str = "Please specify a goal: reach <x> <y>, visit <x> <y> ..., deliver <item> <x> <y> or survive";
+pub const PARSE_ERROR_BAD_GOAL     : &str = "Unknown goal - expecting reach, visit, deliver or survive";
+pub const PARSE_ERROR_BAD_CELL     : &str = "Goal locations must be given as pairs of integers";
+pub const PARSE_ERROR_BAD_ITEM     : &str = "Expecting the name of the item to be delivered";
+pub const PARSE_ERROR_BAD_POINTS   : &str = "Goal points must be a positive integer";
+pub const PARSE_ERROR_BAD_OPTION   : &str = "Expecting goal options of the form by=<id|name> or points=<points>";
+
+const RESULT_PASS : &str = "PASS";
+const RESULT_FAIL : &str = "FAIL";
+
+// *********************************************************************************************************************
+// What has to be achieved
+// *********************************************************************************************************************
+#[derive(Debug, PartialEq, Clone)]
+pub enum Objective {
+  Reach(i32, i32)               // Finish at this location (and not be lost)
+, Visit(Vec<(i32, i32)>)        // Pass through every one of these locations at some point
+, Deliver(String, i32, i32)     // Leave this item lying at this location
+, Survive                       // Don't get lost
+}
+
+// *********************************************************************************************************************
+// Goal definition
+//
+// A goal belongs to one robot, or when no robot is given, to all of them.  A global goal is met when any robot reaches
+// the location, when every location has been visited by some robot, when the item is delivered by any robot, or when
+// no robot has been lost.  An item must have been dropped there by a robot to count as delivered
+// *********************************************************************************************************************
+#[derive(Debug, PartialEq, Clone)]
+pub struct Goal {
+  pub objective : Objective
+, pub robot     : Option<String>    // Id or name
+, pub points    : u32
+}
+
+impl fmt::Display for Goal {
+  fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+    match &self.objective {
+      Objective::Reach(x, y)          => write!(fmt, "reach {} {}", x, y)?
+    , Objective::Visit(cells)         => write!( fmt, "visit {}"
+                                               , cells.iter().map(|(x, y)| format!("{} {}", x, y)).collect::<Vec<String>>().join(", ")
+                                               )?
+    , Objective::Deliver(item, x, y)  => write!(fmt, "deliver {} to {} {}", item, x, y)?
+    , Objective::Survive              => write!(fmt, "survive")?
+    };
+
+    match &self.robot {
+      Some(robot) => write!(fmt, " by {}", robot)
+    , None        => write!(fmt, " by all robots")
+    }
+  }
+}
+
+// *********************************************************************************************************************
+// Goal implementation
+// *********************************************************************************************************************
+impl Goal {
+  pub fn is_met(&self, robots : &[Robot], world : &World) -> bool {
+    // Which robots does this goal apply to?  Naming a robot that doesn't exist is a sure way to fail
+    let robots : Vec<&Robot> = match &self.robot {
+      Some(r) => robots.iter().filter(|robot| robot.is_called(r)).collect()
+    , None    => robots.iter().collect()
+    };
+
+    if robots.is_empty() {
+      return false
+    }
+
+    match &self.objective {
+      Objective::Reach(x, y) => robots.iter().any(|r| !r.is_lost && (r.x, r.y) == (*x, *y))
+    , Objective::Visit(cells) => cells.iter().all(|cell| robots.iter().any(|r| r.visited.contains(cell)))
+    , Objective::Deliver(item, x, y) =>
+        world.contains(x, y) &&
+        world.items_at(x, y).contains(item) &&
+        robots.iter().any(|r| r.deliveries.contains(&(item.clone(), *x, *y)))
+    , Objective::Survive => robots.iter().all(|r| !r.is_lost)
+    }
+  }
+
+  // The same goal with its locations translated, for example from one coordinate convention to another
+  pub fn map_cells(&self, f : impl Fn(i32, i32) -> (i32, i32)) -> Goal {
+    let objective = match &self.objective {
+      Objective::Reach(x, y)         => { let (x, y) = f(*x, *y); Objective::Reach(x, y) }
+    , Objective::Visit(cells)        => Objective::Visit(cells.iter().map(|(x, y)| f(*x, *y)).collect())
+    , Objective::Deliver(item, x, y) => { let (x, y) = f(*x, *y); Objective::Deliver(item.clone(), x, y) }
+    , Objective::Survive             => Objective::Survive
+    };
+
+    Goal { objective, ..self.clone() }
+  }
+}
+
+impl str::FromStr for Goal {
+  type Err = &'static str;
+
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  // <objective> <arguments> [by=<id|name>] [points=<points>]
+  fn from_str(s: &str) -> Result<Goal, Self::Err> {
+    let (args, options) : (Vec<&str>, Vec<&str>) = s.split_ascii_whitespace().partition(|w| !w.contains('='));
+
+    let objective = match args.split_first() {
+      Some((&"reach",   rest)) => match cells(rest)?[..] {
+          [(x, y)] => Objective::Reach(x, y)
+        , _        => return Err(PARSE_ERROR_BAD_CELL)
+        }
+    , Some((&"visit",   rest)) => match cells(rest)? {
+          cells if cells.is_empty() => return Err(PARSE_ERROR_BAD_CELL)
+        , cells                     => Objective::Visit(cells)
+        }
+    , Some((&"deliver", rest)) => match rest.split_first() {
+          Some((item, cell)) => match cells(cell)?[..] {
+            [(x, y)] => Objective::Deliver(item.to_string(), x, y)
+          , _        => return Err(PARSE_ERROR_BAD_CELL)
+          }
+        , None => return Err(PARSE_ERROR_BAD_ITEM)
+        }
+    , Some((&"survive", []))   => Objective::Survive
+    , Some((&"survive", _))    => return Err(PARSE_ERROR_BAD_OPTION)
+    , Some(_)                  => return Err(PARSE_ERROR_BAD_GOAL)
+    , None                     => return Err(PARSE_ERROR_MISSING_GOAL)
+    };
+
+    let mut goal = Goal { objective, robot : None, points : 1 };
+
+    for opt in options {
+      match opt.split_once('=') {
+        Some(("by", robot))      => goal.robot = Some(robot.to_string())
+      , Some(("points", points)) => goal.points = match points.parse::<u32>() {
+          Ok(points) if points > 0 => points
+        , _                        => return Err(PARSE_ERROR_BAD_POINTS)
+        }
+      , _ => return Err(PARSE_ERROR_BAD_OPTION)
+      }
+    }
+
+    Ok(goal)
+  }
+}
+
+// *********************************************************************************************************************
+// Grade a world's robots against its goals
+//
+// One line per goal (with its locations given in the world's coordinate convention), then the overall score
+// *********************************************************************************************************************
+pub fn score(goals : &[Goal], robots : &[Robot], world : &World) -> Vec<String> {
+  let mut report : Vec<String> = vec!();
+  // Points are added up in a wider type, so that no number of goals can overflow the score
+  let mut scored : u64 = 0;
+  let mut total  : u64 = 0;
+
+  for (idx, goal) in goals.iter().enumerate() {
+    let met = goal.is_met(robots, world);
+
+    total += u64::from(goal.points);
+
+    if met {
+      scored += u64::from(goal.points);
+    }
+
+    report.push(format!( "Goal {}: {} (points: {}) {}"
+                       , idx + 1, goal.map_cells(|x, y| world.to_external(&x, &y)), goal.points
+                       , if met { RESULT_PASS } else { RESULT_FAIL }
+                       ));
+  }
+
+  report.push(format!("Score: {}/{} {}", scored, total, if scored == total { RESULT_PASS } else { RESULT_FAIL }));
+  report
+}
+
+// *********************************************************************************************************************
+// Private API
+// *********************************************************************************************************************
+fn cells(args : &[&str]) -> Result<Vec<(i32, i32)>, &'static str> {
+  if !args.len().is_multiple_of(2) {
+    return Err(PARSE_ERROR_BAD_CELL)
+  }
+
+  args.chunks(2)
+      .map(|pair| match (pair[0].parse::<i32>(), pair[1].parse::<i32>()) {
+         (Ok(x), Ok(y)) => Ok((x, y))
+       , _              => Err(PARSE_ERROR_BAD_CELL)
+       })
+      .collect()
+}
+
+// *********************************************************************************************************************
+// Suppose we'd better test it...
+// *********************************************************************************************************************
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::robot::create_robot;
+
+  #[test]
+  fn parse_goals() {
+    assert_eq!( "reach 1 2 by=scout points=3".parse::<Goal>()
+              , Ok(Goal { objective : Objective::Reach(1, 2), robot : Some(String::from("scout")), points : 3 })
+              );
+    assert_eq!( "visit 0 0 1 1".parse::<Goal>().map(|g| g.objective)
+              , Ok(Objective::Visit(vec!((0, 0), (1, 1))))
+              );
+    assert_eq!("deliver crate 4 2".parse::<Goal>().unwrap().to_string(), "deliver crate to 4 2 by all robots");
+    assert_eq!("visit 0 0 1".parse::<Goal>(),      Err(PARSE_ERROR_BAD_CELL));
+    assert_eq!("reach 1 1 points=0".parse::<Goal>(), Err(PARSE_ERROR_BAD_POINTS));
+    assert_eq!("win".parse::<Goal>(),              Err(PARSE_ERROR_BAD_GOAL));
+    assert_eq!("".parse::<Goal>(),                 Err(PARSE_ERROR_MISSING_GOAL));
+  }
+
+  #[test]
+  fn grade_goals() {
+    let mut world = World::new(&5, &3);
+    world.put_item(&0, &0, "crate");
+
+    let mut scout = create_robot("0 0 e name=scout", &mut world).unwrap();
+    let mut other = create_robot("4 2 n", &mut world).unwrap();
+
+    scout.turn_and_move("PFFD", &mut world);
+    other.turn_and_move("F", &mut world);
+
+    let goals : Vec<Goal> = [ "reach 2 0 by=scout", "visit 1 0 4 2", "deliver crate 2 0 by=scout points=2"
+                            , "survive", "survive by=scout", "reach 0 0"
+                            ].iter().map(|g| g.parse().unwrap()).collect();
+
+    assert_eq!( score(&goals, &[scout, other], &world)
+              , vec!( "Goal 1: reach 2 0 by scout (points: 1) PASS"
+                    , "Goal 2: visit 1 0, 4 2 by all robots (points: 1) PASS"
+                    , "Goal 3: deliver crate to 2 0 by scout (points: 2) PASS"
+                    , "Goal 4: survive by all robots (points: 1) FAIL"
+                    , "Goal 5: survive by scout (points: 1) PASS"
+                    , "Goal 6: reach 0 0 by all robots (points: 1) FAIL"
+                    , "Score: 5/7 FAIL"
+                    )
+              );
+  }
+
+  #[test]
+  fn deliveries_and_big_scores() {
+    let mut world = World::new(&5, &3);
+    world.put_item(&2, &0, "crate");
+
+    let robot = create_robot("0 0 e", &mut world).unwrap();
+
+    // An item that was simply put there has not been delivered by anyone
+    let goals : Vec<Goal> = [ "deliver crate 2 0", "survive points=4294967295", "survive points=4294967295"
+                            ].iter().map(|g| g.parse().unwrap()).collect();
+
+    assert_eq!( score(&goals, &[robot], &world).last().map(String::as_str)
+              , Some("Score: 8589934590/8589934591 FAIL")
+              );
+  }
+}
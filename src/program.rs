@@ -0,0 +1,430 @@
+use crate::robot::{Robot, Step};
+use crate::rules::Collisions;
+use crate::validate::{Diagnostic, DIAG_INVALID_COMMAND, DIAG_NO_INSTRUCTIONS, VALID_COMMANDS};
+use crate::world::{Outlook, World};
+use crate::trace::Trace;
+
+const LIB_NAME     : &str  = module_path!();
+const TRACE_ACTIVE : &bool = &false;
+
+// Stop runaway loops from hanging the simulator
+pub const MAX_PROGRAM_STEPS : u32 = 10_000;
+
+const KEYWORD_IF     : &str = "IF";
+const KEYWORD_ELSE   : &str = "ELSE";
+const KEYWORD_WHILE  : &str = "WHILE";
+const KEYWORD_REPEAT : &str = "REPEAT";
+const KEYWORD_NOT    : &str = "NOT";
+
+const BLOCK_START : char = '{';
+const BLOCK_END   : char = '}';
+
+pub const DIAG_EXPECTED_COUNT     : &str = "REPEAT must be followed by a positive whole number";
+pub const DIAG_EXPECTED_CONDITION : &str = "Expecting a condition FREE, EDGE, SCENTED or OCCUPIED";
+pub const DIAG_EXPECTED_BLOCK     : &str = "Expecting '{' to start a block";
+pub const DIAG_UNCLOSED_BLOCK     : &str = "Missing '}' at end of block";
+pub const DIAG_UNEXPECTED_TEXT    : &str = "Unexpected text";
+
+// *********************************************************************************************************************
+// What a robot can test about the location ahead of it
+// *********************************************************************************************************************
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Condition {
+  Free        // Ahead is an empty location
+, Edge        // Ahead is the edge of the world (scented or not)
+, Scented     // Ahead is the edge of the world and a previous robot was lost going that way
+, Occupied    // Ahead is another robot
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Test {
+  negated   : bool
+, condition : Condition
+}
+
+impl Test {
+  fn holds(&self, robot : &Robot, world : &World) -> bool {
+    let outlook = robot.ahead(world);
+
+    let found = match self.condition {
+      Condition::Free     => outlook == Outlook::Free
+    , Condition::Edge     => outlook == Outlook::Edge || outlook == Outlook::Scent
+    , Condition::Scented  => outlook == Outlook::Scent
+    , Condition::Occupied => matches!(outlook, Outlook::Occupied(_) | Outlook::Obstacle)
+    };
+
+    found != self.negated
+  }
+}
+
+// *********************************************************************************************************************
+// The compiled form of an instruction line
+//
+// Blocks are flattened into jumps so that the program can be run one operation at a time
+// *********************************************************************************************************************
+#[derive(Debug, PartialEq, Clone)]
+enum Op {
+  Obey(char)                // Pass a move/turn command to the robot
+, Jump(usize)               // Carry on from this operation
+, JumpUnless(Test, usize)   // Carry on from this operation if the test fails
+, PushCount(u32)            // Start counting the iterations of a REPEAT block
+, CountDown(usize)          // End the REPEAT block by jumping to this operation once the count reaches zero
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Program {
+  ops : Vec<Op>
+}
+
+// *********************************************************************************************************************
+// The outcome of running a program
+// *********************************************************************************************************************
+#[derive(Debug, PartialEq)]
+pub struct ProgramRun {
+  pub steps     : Vec<Step>   // The outcome of every move/turn command obeyed
+, pub exhausted : bool        // The step budget ran out before the program finished
+}
+
+// *********************************************************************************************************************
+// Does this line need compiling, or is it just a plain sequence of move/turn commands?
+// *********************************************************************************************************************
+pub fn is_program(line : &str) -> bool {
+  line.contains(BLOCK_START) || line.contains(BLOCK_END) ||
+  line.split_ascii_whitespace().any(|w| is_keyword(&w.to_ascii_uppercase()))
+}
+
+fn is_keyword(word : &str) -> bool {
+  [KEYWORD_IF, KEYWORD_ELSE, KEYWORD_WHILE, KEYWORD_REPEAT, KEYWORD_NOT].contains(&word)
+}
+
+// *********************************************************************************************************************
+// Program implementation
+// *********************************************************************************************************************
+impl Program {
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  // Grammar (keywords and commands are case-insensitive):
+  //
+  //   program   := statement*
+  //   statement := commands
+  //              | REPEAT count { program }
+  //              | WHILE test { program }
+  //              | IF test { program } [ELSE { program }]
+  //   test      := [NOT] (FREE | EDGE | SCENTED | OCCUPIED)
+  //   commands  := one or more of F, L, R, W, P, D
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  pub fn compile(line : &str) -> Result<Program, Diagnostic> {
+    const FN_NAME : &str = "compile";
+
+    let trace_boundary = Trace::make_boundary_trace_fn(TRACE_ACTIVE, LIB_NAME, FN_NAME);
+    let trace          = Trace::make_trace_fn(TRACE_ACTIVE, LIB_NAME, FN_NAME);
+
+    trace_boundary(&Some(true));
+
+    let mut compiler = Compiler { tokens : tokenise(line), next : 0, ops : vec!() };
+    compiler.block(false)?;
+
+    if compiler.ops.is_empty() {
+      return Err(Diagnostic { column : 1, found : None, message : DIAG_NO_INSTRUCTIONS })
+    }
+
+    trace(&format!("Compiled {} operations", compiler.ops.len()));
+    trace_boundary(&Some(false));
+
+    Ok(Program { ops : compiler.ops })
+  }
+
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  // Run the program until it finishes, the robot is lost or stranded without energy, or the step budget runs out
+  //
+  // Every operation counts against the budget, not just the move/turn commands, so even an empty loop terminates
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  pub fn run(&self, robot : &mut Robot, world : &mut World, budget : u32) -> ProgramRun {
+    let mut result = ProgramRun { steps : vec!(), exhausted : false };
+    let mut counts : Vec<u32> = vec!();
+    let mut pc     = 0;
+    let mut used   = 0;
+
+    while pc < self.ops.len() && !robot.is_lost && !robot.is_stranded(world) {
+      if used == budget {
+        result.exhausted = true;
+        break
+      }
+
+      used += 1;
+
+      pc = match self.ops[pc] {
+        Op::Obey(cmd) => {
+          let step   = robot.obey(cmd, world);
+          let halted = step.is_blocked() && world.rules.collisions == Collisions::Halt;
+
+          result.steps.push(step);
+
+          if halted {
+            break
+          }
+
+          pc + 1
+        }
+      , Op::Jump(to)             => to
+      , Op::JumpUnless(test, to) => if test.holds(robot, world) { pc + 1 } else { to }
+      , Op::PushCount(n)         => {
+          counts.push(n);
+          pc + 1
+        }
+      , Op::CountDown(to) => match counts.last_mut() {
+          Some(n) if *n > 0 => {
+            *n -= 1;
+            pc + 1
+          }
+        , _ => {
+            counts.pop();
+            to
+          }
+        }
+      };
+    }
+
+    result
+  }
+}
+
+// *********************************************************************************************************************
+// Private API
+// *********************************************************************************************************************
+#[derive(Debug)]
+struct Token {
+  column : usize    // One-based character position within the line
+, text   : String   // As written
+, word   : String   // Uppercased, for matching keywords and commands
+}
+
+// Words are separated by whitespace, but braces always stand alone
+fn tokenise(line : &str) -> Vec<Token> {
+  let mut tokens : Vec<Token> = vec!();
+  let mut word   : Option<Token> = None;
+
+  for (idx, c) in line.chars().enumerate() {
+    if c.is_whitespace() || c == BLOCK_START || c == BLOCK_END {
+      tokens.extend(word.take());
+
+      if !c.is_whitespace() {
+        tokens.push(Token { column : idx + 1, text : c.to_string(), word : c.to_string() });
+      }
+    }
+    else {
+      let token = word.get_or_insert_with(|| Token { column : idx + 1, text : String::new(), word : String::new() });
+      token.text.push(c);
+      token.word.push(c.to_ascii_uppercase());
+    }
+  }
+
+  tokens.extend(word);
+  tokens
+}
+
+fn unexpected(token : &Token, message : &'static str) -> Diagnostic {
+  Diagnostic { column : token.column, found : token.text.chars().next(), message }
+}
+
+struct Compiler {
+  tokens : Vec<Token>
+, next   : usize
+, ops    : Vec<Op>
+}
+
+impl Compiler {
+  fn peek(&self) -> Option<&Token> {
+    self.tokens.get(self.next)
+  }
+
+  fn advance(&mut self) -> Option<&Token> {
+    self.next += 1;
+    self.tokens.get(self.next - 1)
+  }
+
+  fn end_column(&self) -> usize {
+    self.tokens.last().map_or(1, |t| t.column + t.text.chars().count())
+  }
+
+  // Compile statements up to the end of the line or (when nested) the closing brace
+  fn block(&mut self, nested : bool) -> Result<(), Diagnostic> {
+    loop {
+      let token = match self.peek() {
+        Some(token) => token
+      , None if nested => return Err(Diagnostic { column : self.end_column(), found : None, message : DIAG_UNCLOSED_BLOCK })
+      , None => return Ok(())
+      };
+
+      match token.word.as_str() {
+        "}" if nested => {
+          self.next += 1;
+          return Ok(())
+        }
+      , "}" | "{" | KEYWORD_ELSE | KEYWORD_NOT => return Err(unexpected(token, DIAG_UNEXPECTED_TEXT))
+      , KEYWORD_REPEAT => self.repeat()?
+      , KEYWORD_WHILE  => self.while_loop()?
+      , KEYWORD_IF     => self.if_else()?
+      , _              => self.commands()?
+      }
+    }
+  }
+
+  fn commands(&mut self) -> Result<(), Diagnostic> {
+    let token = self.advance().unwrap();
+
+    for (offset, (c, cmd)) in token.text.chars().zip(token.word.chars()).enumerate() {
+      if !VALID_COMMANDS.contains(&cmd) {
+        return Err(Diagnostic { column : token.column + offset, found : Some(c), message : DIAG_INVALID_COMMAND })
+      }
+    }
+
+    let cmds : Vec<Op> = token.word.chars().map(Op::Obey).collect();
+    self.ops.extend(cmds);
+    Ok(())
+  }
+
+  fn repeat(&mut self) -> Result<(), Diagnostic> {
+    let keyword = self.advance().unwrap();
+    let column  = keyword.column;
+
+    let count = match self.advance() {
+      Some(token) => token.text.parse::<u32>().ok().filter(|n| *n > 0).ok_or_else(|| unexpected(token, DIAG_EXPECTED_COUNT))?
+    , None        => return Err(Diagnostic { column, found : None, message : DIAG_EXPECTED_COUNT })
+    };
+
+    // The count is pushed once, then decremented at the top of each iteration
+    self.ops.push(Op::PushCount(count));
+    let top = self.ops.len();
+    self.ops.push(Op::CountDown(0));
+    self.body()?;
+    self.ops.push(Op::Jump(top));
+    self.ops[top] = Op::CountDown(self.ops.len());
+    Ok(())
+  }
+
+  fn while_loop(&mut self) -> Result<(), Diagnostic> {
+    self.next += 1;
+
+    let test = self.test()?;
+    let top  = self.ops.len();
+
+    self.ops.push(Op::JumpUnless(test, 0));
+    self.body()?;
+    self.ops.push(Op::Jump(top));
+    self.ops[top] = Op::JumpUnless(test, self.ops.len());
+    Ok(())
+  }
+
+  fn if_else(&mut self) -> Result<(), Diagnostic> {
+    self.next += 1;
+
+    let test = self.test()?;
+    let cond = self.ops.len();
+
+    self.ops.push(Op::JumpUnless(test, 0));
+    self.body()?;
+
+    if self.peek().is_some_and(|t| t.word == KEYWORD_ELSE) {
+      self.next += 1;
+
+      let skip = self.ops.len();
+      self.ops.push(Op::Jump(0));
+      self.ops[cond] = Op::JumpUnless(test, self.ops.len());
+      self.body()?;
+      self.ops[skip] = Op::Jump(self.ops.len());
+    }
+    else {
+      self.ops[cond] = Op::JumpUnless(test, self.ops.len());
+    }
+
+    Ok(())
+  }
+
+  fn test(&mut self) -> Result<Test, Diagnostic> {
+    let end     = self.end_column();
+    let negated = self.peek().is_some_and(|t| t.word == KEYWORD_NOT);
+
+    if negated {
+      self.next += 1;
+    }
+
+    let token = self.advance().ok_or(Diagnostic { column : end, found : None, message : DIAG_EXPECTED_CONDITION })?;
+
+    let condition = match token.word.as_str() {
+      "FREE"              => Condition::Free
+    , "EDGE"              => Condition::Edge
+    , "SCENTED" | "SCENT" => Condition::Scented
+    , "OCCUPIED"          => Condition::Occupied
+    , _                   => return Err(unexpected(token, DIAG_EXPECTED_CONDITION))
+    };
+
+    Ok(Test { negated, condition })
+  }
+
+  fn body(&mut self) -> Result<(), Diagnostic> {
+    match self.advance() {
+      Some(token) if token.word == "{" => self.block(true)
+    , Some(token) => Err(unexpected(token, DIAG_EXPECTED_BLOCK))
+    , None        => Err(Diagnostic { column : self.end_column(), found : None, message : DIAG_EXPECTED_BLOCK })
+    }
+  }
+}
+
+// *********************************************************************************************************************
+// Suppose we'd better test it...
+// *********************************************************************************************************************
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::heading::Heading;
+
+  fn run(line : &str, robot : &mut Robot, world : &mut World) -> ProgramRun {
+    Program::compile(line).unwrap().run(robot, world, MAX_PROGRAM_STEPS)
+  }
+
+  #[test]
+  fn loops_and_conditions() {
+    let mut world = World::new(&5, &5);
+    let mut robot = Robot::new(0, 0, 0, Heading::North);
+
+    world.place_robot_at(&robot.id, &robot.x, &robot.y);
+
+    // Walk to the top edge, then turn right twice (to face south) only if there's an edge ahead
+    let result = run("while free { F } if edge { RR } else { L }", &mut robot, &mut world);
+    assert_eq!(robot.to_string(), "0 4 S");
+    assert_eq!(result.steps.len(), 6);
+    assert!(!result.exhausted);
+
+    // Walk round the edge of the world
+    run("REPEAT 4 { WHILE NOT EDGE { F } L }", &mut robot, &mut world);
+    assert_eq!(robot.to_string(), "0 4 S");
+
+    // Nested loops
+    run("L repeat 2 { repeat 2 { F } }", &mut robot, &mut world);
+    assert_eq!(robot.to_string(), "4 4 E");
+  }
+
+  #[test]
+  fn step_budget() {
+    let mut world = World::new(&3, &3);
+    let mut robot = Robot::new(0, 1, 1, Heading::North);
+
+    let result = Program::compile("WHILE NOT EDGE { L R }").unwrap().run(&mut robot, &mut world, 100);
+    assert!(result.exhausted);
+    assert_eq!(robot.to_string(), "1 1 N");
+  }
+
+  #[test]
+  fn compile_errors() {
+    let error = |line : &str| Program::compile(line).unwrap_err().to_string();
+
+    assert_eq!(error("REPEAT x { F }"),    "column 8: REPEAT must be followed by a positive whole number 'x'");
+    assert_eq!(error("WHILE HAPPY { F }"), "column 7: Expecting a condition FREE, EDGE, SCENTED or OCCUPIED 'H'");
+    assert_eq!(error("IF FREE F"),         "column 9: Expecting '{' to start a block 'F'");
+    assert_eq!(error("IF FREE { F"),       "column 12: Missing '}' at end of block");
+    assert_eq!(error("F } R"),             "column 3: Unexpected text '}'");
+    assert_eq!(error("REPEAT 2 { FxF }"),  "column 13: Invalid move/turn command 'x'");
+    assert!(is_program("repeat 2 {F}"));
+    assert!(!is_program("FRRFLLFF"));
+  }
+}
@@ -0,0 +1,85 @@
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+use std::path::Path;
+
+use crate::input::{classify, read_file, Line, SourceLine, INCLUDE_KEYWORD};
+use crate::rules::Rules;
+use crate::session::Session;
+use crate::validate::Validation;
+use crate::world::{Dialect, Limits};
+use crate::trace::Trace;
+
+const LIB_NAME     : &str  = module_path!();
+const TRACE_ACTIVE : &bool = &false;
+
+// *********************************************************************************************************************
+// Read lines from stdin (with line editing and history when stdin is a terminal) until EOF
+// *********************************************************************************************************************
+pub fn run(validation : Validation, dialect : Dialect, limits : Limits, rules : Rules) -> rustyline::Result<()> {
+  const FN_NAME : &str = "run";
+
+  let trace_boundary = Trace::make_boundary_trace_fn(TRACE_ACTIVE, LIB_NAME, FN_NAME);
+  let trace          = Trace::make_trace_fn(TRACE_ACTIVE, LIB_NAME, FN_NAME);
+
+  trace_boundary(&Some(true));
+
+  let mut editor  = DefaultEditor::new()?;
+  let mut session = Session::new(validation).with_dialect(dialect).with_limits(limits).with_rules(rules);
+
+  loop {
+    let line = match editor.readline(&format!("{} : ", session.prompt())) {
+      Ok(line) => line
+    , Err(ReadlineError::Eof)         => break
+    , Err(ReadlineError::Interrupted) => break
+    , Err(err)                        => return Err(err)
+    };
+
+    if line.trim().is_empty() {
+      continue;
+    }
+
+    let _ = editor.add_history_entry(line.as_str());
+    trace(&format!("Read line '{}'", line));
+
+    match classify(&line) {
+      Line::Blank | Line::Comment => ()
+    , Line::Include("")           => eprintln!("Error: {} needs a file name", INCLUDE_KEYWORD)
+    , Line::Include(file)         =>
+        match read_file(Path::new(file)) {
+          Ok(lines) => lines.iter().for_each(|line| respond(&mut session, &line.text, Some(line)))
+        , Err(err)  => eprintln!("Error: {}", err)
+        }
+    , Line::Command(cmd) => respond(&mut session, cmd, None)
+    }
+  }
+
+  for line in session.final_report() {
+    println!("{}", line);
+  }
+
+  trace_boundary(&Some(false));
+  Ok(())
+}
+
+// *********************************************************************************************************************
+// Private API
+// *********************************************************************************************************************
+
+// Print whatever the session made of a line, saying which file it came from if it was included
+fn respond(session : &mut Session, line : &str, source : Option<&SourceLine>) {
+  match session.handle_line(line) {
+    Ok(reply) => {
+      for w in reply.warnings {
+        eprintln!("Warning: {}", w);
+      }
+
+      for o in reply.output {
+        println!("{}", o);
+      }
+    }
+  , Err(err) => match source {
+      Some(source) => eprintln!("Error at {}: {}", source, err)
+    , None         => eprintln!("Error: {}", err)
+    }
+  }
+}
@@ -0,0 +1,451 @@
+use std::collections::BTreeMap;
+use std::io;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tiny_http::{Header, Response, Server};
+
+use crate::coords::Coords;
+use crate::heading::Heading;
+use crate::kind::Kind;
+use crate::robot::{follow_pushes, place_robot, Robot};
+use crate::rules::Rules;
+use crate::session::Orders;
+use crate::validate::Validation;
+use crate::world::{Limits, World};
+use crate::trace::Trace;
+
+const LIB_NAME     : &str  = module_path!();
+const TRACE_ACTIVE : &bool = &false;
+
+pub const DEFAULT_HTTP_PORT : u16 = 8080;
+
+const STATUS_OK          : u16 = 200;
+const STATUS_CREATED     : u16 = 201;
+const STATUS_BAD_REQUEST : u16 = 400;
+const STATUS_NOT_FOUND   : u16 = 404;
+const STATUS_CONFLICT    : u16 = 409;
+
+const ERROR_BAD_WORLD_NAME : &str = "A world's name must not be empty or contain '/', '?' or whitespace";
+
+// HTTP status, error code and message
+type ApiError = (u16, &'static str, String);
+
+// *********************************************************************************************************************
+// Request bodies
+// *********************************************************************************************************************
+#[derive(Debug, Deserialize)]
+struct NewWorld {
+  name   : Option<String>
+, width  : i32
+, height : i32
+, coords : Option<String>     // For example "1 top-left"
+}
+
+#[derive(Debug, Deserialize)]
+struct NewRobot {
+  x       : i32
+, y       : i32
+, heading : String
+, id      : Option<i32>
+, name    : Option<String>
+, energy  : Option<u32>
+, r#type  : Option<String>
+, cargo   : Option<usize>
+}
+
+#[derive(Debug, Deserialize)]
+struct Instructions {
+  instructions : String
+}
+
+// *********************************************************************************************************************
+// Response bodies
+// *********************************************************************************************************************
+#[derive(Debug, Serialize)]
+struct RobotView {
+  id      : i32
+, name    : Option<String>
+, x       : i32
+, y       : i32
+, heading : String
+, r#type  : String
+, lost    : bool
+, energy  : Option<u32>
+, cargo   : Vec<String>
+, report  : String
+}
+
+impl RobotView {
+  // Locations are given in the world's coordinate convention
+  fn from(robot : &Robot, world : &World) -> RobotView {
+    let (x, y) = world.to_external(&robot.x, &robot.y);
+
+    RobotView {
+      id      : robot.id
+    , name    : robot.name.clone()
+    , x
+    , y
+    , heading : robot.heading.to_string()
+    , r#type  : robot.kind.to_string()
+    , lost    : robot.is_lost
+    , energy  : robot.energy
+    , cargo   : robot.cargo.clone()
+    , report  : robot.report(world)
+    }
+  }
+}
+
+#[derive(Debug, Serialize)]
+struct ScentView {
+  x       : i32
+, y       : i32
+, heading : String
+}
+
+// *********************************************************************************************************************
+// A world and the robots in it
+// *********************************************************************************************************************
+#[derive(Debug)]
+struct WorldEntry {
+  world  : World
+, robots : Vec<Robot>
+}
+
+impl WorldEntry {
+  fn view(&self, name : &str) -> Value {
+    json!({
+      "name"      : name
+    , "width"     : self.world.width
+    , "height"    : self.world.height
+    , "coords"    : self.world.coords.to_string()
+    , "robots"    : self.robot_views()
+    , "rendering" : self.world.to_string()
+    })
+  }
+
+  fn robot_views(&self) -> Vec<RobotView> {
+    self.robots.iter().map(|robot| RobotView::from(robot, &self.world)).collect()
+  }
+}
+
+// *********************************************************************************************************************
+// Api definition
+// *********************************************************************************************************************
+#[derive(Debug)]
+pub struct Api {
+  worlds     : BTreeMap<String, WorldEntry>
+, validation : Validation
+, limits     : Limits         // The largest world that may be created
+, rules      : Rules          // The rules new worlds are played by
+}
+
+// *********************************************************************************************************************
+// Api implementation
+// *********************************************************************************************************************
+impl Api {
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  // Route a request to its handler, returning the HTTP status and JSON response body
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  pub fn handle(&mut self, method : &str, path : &str, body : &str) -> (u16, Value) {
+    const FN_NAME : &str = "handle";
+
+    let trace_boundary = Trace::make_boundary_trace_fn(TRACE_ACTIVE, LIB_NAME, FN_NAME);
+    let trace          = Trace::make_trace_fn(TRACE_ACTIVE, LIB_NAME, FN_NAME);
+
+    trace_boundary(&Some(true));
+    trace(&format!("{} {}", method, path));
+
+    let segments : Vec<&str> = path.split('?').next().unwrap_or("").split('/').filter(|s| !s.is_empty()).collect();
+
+    let response = match (method, segments.as_slice()) {
+      ("GET",  ["worlds"])                                 => Ok((STATUS_OK, json!(self.worlds.keys().collect::<Vec<&String>>())))
+    , ("POST", ["worlds"])                                 => self.new_world(body)
+    , ("GET",  ["worlds", w])                              => self.world(w).map(|entry| (STATUS_OK, entry.view(w)))
+    , ("GET",  ["worlds", w, "scents"])                    => self.scents(w)
+    , ("GET",  ["worlds", w, "robots"])                    => self.world(w).map(|entry| (STATUS_OK, json!(entry.robot_views())))
+    , ("POST", ["worlds", w, "robots"])                    => self.new_robot(w, body)
+    , ("GET",  ["worlds", w, "robots", r])                 => self.robot(w, r).map(|robot| (STATUS_OK, json!(RobotView::from(robot, &self.worlds[*w].world))))
+    , ("POST", ["worlds", w, "robots", r, "instructions"]) => self.instructions(w, r, body)
+    , _ => Err((STATUS_NOT_FOUND, "NOT_FOUND", format!("No such resource: {} {}", method, path)))
+    };
+
+    trace_boundary(&Some(false));
+
+    match response {
+      Ok(ok)                       => ok
+    , Err((status, code, message)) => (status, json!({ "error" : { "code" : code, "message" : message } }))
+    }
+  }
+
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  // Resource handlers
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  fn new_world(&mut self, body : &str) -> Result<(u16, Value), ApiError> {
+    let req  = parse_body::<NewWorld>(body)?;
+    let dims = self.limits.dimensions(req.width, req.height).map_err(|e| (STATUS_BAD_REQUEST, "INVALID_WORLD", e.to_string()))?;
+    let name = req.name.unwrap_or_else(|| self.unused_name());
+
+    // The name becomes part of the world's path, so it must survive the trip back through the router
+    if name.is_empty() || name.contains(|c : char| c == '/' || c == '?' || c.is_whitespace()) {
+      return Err((STATUS_BAD_REQUEST, "INVALID_WORLD", String::from(ERROR_BAD_WORLD_NAME)))
+    }
+
+    let mut world = World::new(&dims.width, &dims.height);
+    world.rules   = self.rules;
+
+    if let Some(coords) = req.coords {
+      world.coords = coords.parse::<Coords>().map_err(|e| (STATUS_BAD_REQUEST, "INVALID_WORLD", e.to_string()))?;
+    }
+
+    if self.worlds.contains_key(&name) {
+      return Err((STATUS_CONFLICT, "DUPLICATE_WORLD", format!("A world called {} already exists", name)))
+    }
+
+    let entry = WorldEntry { world, robots : vec!() };
+    let view  = entry.view(&name);
+
+    self.worlds.insert(name, entry);
+    Ok((STATUS_CREATED, view))
+  }
+
+  // The first of world-1, world-2, ... that nobody has taken yet
+  fn unused_name(&self) -> String {
+    let mut n = self.worlds.len() + 1;
+
+    while self.worlds.contains_key(&format!("world-{}", n)) {
+      n += 1;
+    }
+
+    format!("world-{}", n)
+  }
+
+  fn scents(&self, w : &str) -> Result<(u16, Value), ApiError> {
+    let world = &self.world(w)?.world;
+
+    let scents : Vec<ScentView> = world.scents().into_iter()
+      .map(|(x, y, h)| {
+        let (x, y) = world.to_external(&x, &y);
+        ScentView { x, y, heading : h.to_string() }
+      })
+      .collect();
+
+    Ok((STATUS_OK, json!(scents)))
+  }
+
+  fn new_robot(&mut self, w : &str, body : &str) -> Result<(u16, Value), ApiError> {
+    let req     = parse_body::<NewRobot>(body)?;
+    let heading = req.heading.parse::<Heading>().map_err(|e| (STATUS_BAD_REQUEST, "INVALID_ROBOT", e.to_string()))?;
+    let kind    = req.r#type.as_deref().map_or(Ok(Kind::Rover), str::parse::<Kind>)
+                     .map_err(|e| (STATUS_BAD_REQUEST, "INVALID_ROBOT", e.to_string()))?;
+    let entry   = self.world_mut(w)?;
+
+    let (x, y)    = entry.world.to_internal(&req.x, &req.y);
+    let mut robot = Robot::new(req.id.unwrap_or(-1), x, y, heading);
+    robot.name     = req.name;
+    robot.kind     = kind;
+
+    if let Some(limit) = req.cargo {
+      robot.cargo_limit = limit;
+    }
+    robot.energy   = req.energy;
+    robot.capacity = req.energy;
+
+    let robot = place_robot(robot, &mut entry.world).map_err(|e| (STATUS_BAD_REQUEST, "INVALID_ROBOT", e.to_string()))?;
+    let view  = json!(RobotView::from(&robot, &entry.world));
+
+    entry.robots.push(robot);
+    Ok((STATUS_CREATED, view))
+  }
+
+  fn instructions(&mut self, w : &str, r : &str, body : &str) -> Result<(u16, Value), ApiError> {
+    let req = parse_body::<Instructions>(body)?;
+    // An unknown world or robot is reported as such, however bad the instructions
+    self.robot(w, r)?;
+
+    let orders = Orders::check(&req.instructions, self.validation).map_err(|diags| {
+      let message = diags.iter().map(|d| d.to_string()).collect::<Vec<String>>().join("; ");
+      (STATUS_BAD_REQUEST, "INVALID_INSTRUCTIONS", message)
+    })?;
+
+    let entry = self.world_mut(w)?;
+    let robot = entry.robots.iter_mut().find(|robot| robot.is_called(r))
+                            .ok_or_else(|| (STATUS_NOT_FOUND, "UNKNOWN_ROBOT", format!("No robot with id or name {}", r)))?;
+
+    if robot.is_lost {
+      return Err((STATUS_CONFLICT, "ROBOT_LOST", format!("Robot {} is lost and cannot obey instructions", robot.label())))
+    }
+
+    let (steps, warnings) = orders.obey(robot, &mut entry.world);
+
+    let view = RobotView::from(robot, &entry.world);
+    follow_pushes(&mut entry.robots, &steps, &entry.world);
+
+    Ok((STATUS_OK, json!({ "robot" : view, "warnings" : warnings })))
+  }
+
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  // Lookups
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  fn world(&self, w : &str) -> Result<&WorldEntry, ApiError> {
+    self.worlds.get(w).ok_or_else(|| (STATUS_NOT_FOUND, "UNKNOWN_WORLD", format!("No world called {}", w)))
+  }
+
+  fn world_mut(&mut self, w : &str) -> Result<&mut WorldEntry, ApiError> {
+    self.worlds.get_mut(w).ok_or_else(|| (STATUS_NOT_FOUND, "UNKNOWN_WORLD", format!("No world called {}", w)))
+  }
+
+  fn robot(&self, w : &str, r : &str) -> Result<&Robot, ApiError> {
+    self.world(w)?.robots.iter().find(|robot| robot.is_called(r))
+        .ok_or_else(|| (STATUS_NOT_FOUND, "UNKNOWN_ROBOT", format!("No robot with id or name {}", r)))
+  }
+
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  // Constructor
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  pub fn new(validation : Validation) -> Api {
+    Api {
+      worlds : BTreeMap::new()
+    , validation
+    , limits : Limits::DEFAULT
+    , rules  : Rules::default()
+    }
+  }
+
+  // Allow larger (or only smaller) worlds than usual
+  pub fn with_limits(mut self, limits : Limits) -> Api {
+    self.limits = limits;
+    self
+  }
+
+  // Play every new world by other than the default rules
+  pub fn with_rules(mut self, rules : Rules) -> Api {
+    self.rules = rules;
+    self
+  }
+}
+
+// *********************************************************************************************************************
+// Serve the API on a local HTTP port
+// *********************************************************************************************************************
+pub fn serve(port : u16, validation : Validation, limits : Limits, rules : Rules) -> io::Result<()> {
+  let server = Server::http(("127.0.0.1", port)).map_err(io::Error::other)?;
+  let mut api = Api::new(validation).with_limits(limits).with_rules(rules);
+
+  eprintln!("Listening on http://{}", server.server_addr());
+
+  for mut request in server.incoming_requests() {
+    let mut body = String::new();
+
+    let (status, value) = match request.as_reader().read_to_string(&mut body) {
+      Ok(_)    => api.handle(request.method().as_str(), request.url(), &body)
+    , Err(err) => (STATUS_BAD_REQUEST, json!({ "error" : { "code" : "INVALID_BODY", "message" : err.to_string() } }))
+    };
+
+    let content_type = Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap();
+    let response     = Response::from_string(value.to_string()).with_status_code(status).with_header(content_type);
+
+    if let Err(err) = request.respond(response) {
+      eprintln!("Failed to send response: {}", err);
+    }
+  }
+
+  Ok(())
+}
+
+// *********************************************************************************************************************
+// Private API
+// *********************************************************************************************************************
+fn parse_body<'a, T : Deserialize<'a>>(body : &'a str) -> Result<T, ApiError> {
+  serde_json::from_str::<T>(body).map_err(|e| (STATUS_BAD_REQUEST, "INVALID_BODY", e.to_string()))
+}
+
+// *********************************************************************************************************************
+// Suppose we'd better test it...
+// *********************************************************************************************************************
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn worlds_and_robots() {
+    let mut api = Api::new(Validation::Strict);
+
+    let (status, world) = api.handle("POST", "/worlds", r#"{ "name" : "mars", "width" : 5, "height" : 3 }"#);
+    assert_eq!((status, world["name"].as_str()), (STATUS_CREATED, Some("mars")));
+
+    let (status, robot) = api.handle("POST", "/worlds/mars/robots", r#"{ "x" : 3, "y" : 2, "heading" : "N", "name" : "scout" }"#);
+    assert_eq!((status, robot["id"].as_i64()), (STATUS_CREATED, Some(0)));
+
+    let (status, result) = api.handle("POST", "/worlds/mars/robots/scout/instructions", r#"{ "instructions" : "F" }"#);
+    assert_eq!(status, STATUS_OK);
+    assert_eq!(result["robot"]["report"], "scout: 3 2 N LOST");
+
+    let (_, scents) = api.handle("GET", "/worlds/mars/scents", "");
+    assert_eq!(scents, json!([{ "x" : 3, "y" : 2, "heading" : "N" }]));
+
+    let (status, world) = api.handle("GET", "/worlds/mars", "");
+    assert_eq!(status, STATUS_OK);
+    assert_eq!(world["rendering"], api.worlds["mars"].world.to_string());
+  }
+
+  #[test]
+  fn coordinate_conventions() {
+    let mut api = Api::new(Validation::Strict);
+
+    let (_, world) = api.handle("POST", "/worlds", r#"{ "name" : "screen", "width" : 5, "height" : 3, "coords" : "1 top-left" }"#);
+    assert_eq!(world["coords"], "1 top-left");
+
+    // (1,1) is the top-left location, so the robot falls off the top of the world
+    api.handle("POST", "/worlds/screen/robots", r#"{ "x" : 1, "y" : 1, "heading" : "N" }"#);
+    assert_eq!(api.worlds["screen"].robots[0].y, 2);
+
+    let (_, result) = api.handle("POST", "/worlds/screen/robots/0/instructions", r#"{ "instructions" : "RFF" }"#);
+    assert_eq!((result["robot"]["x"].as_i64(), result["robot"]["y"].as_i64()), (Some(3), Some(1)));
+    assert_eq!(result["robot"]["report"], "3 1 E");
+
+    let (status, error) = api.handle("POST", "/worlds", r#"{ "width" : 5, "height" : 3, "coords" : "2 middle" }"#);
+    assert_eq!((status, error["error"]["code"].as_str()), (STATUS_BAD_REQUEST, Some("INVALID_WORLD")));
+  }
+
+  #[test]
+  fn errors() {
+    let mut api = Api::new(Validation::Strict);
+
+    assert_eq!(api.handle("GET", "/worlds/venus", "").0, STATUS_NOT_FOUND);
+    assert_eq!(api.handle("POST", "/worlds", r#"{ "width" : 99, "height" : 3 }"#).1["error"]["code"], "INVALID_WORLD");
+    assert_eq!(api.handle("POST", "/worlds", r#"{ "width" : 5 }"#).1["error"]["code"], "INVALID_BODY");
+
+    api.handle("POST", "/worlds", r#"{ "width" : 5, "height" : 3 }"#);
+    assert_eq!(api.handle("POST", "/worlds/world-1/robots", r#"{ "x" : 1, "y" : 1, "heading" : "Q" }"#).1["error"]["code"], "INVALID_ROBOT");
+    assert_eq!(api.handle("POST", "/worlds/world-1/robots", r#"{ "x" : 1, "y" : 1, "heading" : "E" }"#).0, STATUS_CREATED);
+    assert_eq!( api.handle("POST", "/worlds/world-1/robots/0/instructions", r#"{ "instructions" : "FX" }"#).1["error"]["code"]
+              , "INVALID_INSTRUCTIONS"
+              );
+    assert_eq!( api.handle("POST", "/worlds/world-1/robots/7/instructions", r#"{ "instructions" : "FX" }"#).1["error"]["code"]
+              , "UNKNOWN_ROBOT"
+              );
+  }
+
+  #[test]
+  fn unreachable_world_names() {
+    let mut api = Api::new(Validation::Strict);
+
+    for name in ["", "a/b", "a?b", "a b"] {
+      let (status, error) = api.handle("POST", "/worlds", &format!(r#"{{ "name" : "{}", "width" : 5, "height" : 3 }}"#, name));
+      assert_eq!((status, error["error"]["code"].as_str()), (STATUS_BAD_REQUEST, Some("INVALID_WORLD")), "{:?}", name);
+    }
+
+    assert!(api.worlds.is_empty());
+  }
+
+  #[test]
+  fn unused_world_names() {
+    let mut api = Api::new(Validation::Strict);
+
+    api.handle("POST", "/worlds", r#"{ "name" : "world-2", "width" : 5, "height" : 3 }"#);
+    assert_eq!(api.handle("POST", "/worlds", r#"{ "width" : 5, "height" : 3 }"#).1["name"], "world-3");
+    assert_eq!(api.handle("POST", "/worlds", r#"{ "width" : 5, "height" : 3 }"#).1["name"], "world-4");
+    assert_eq!(api.worlds.len(), 3);
+  }
+}
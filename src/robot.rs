@@ -1,51 +1,104 @@
 use std::{str, fmt};
-use std::io::prelude::*;
-use std::io::BufReader;
+use std::collections::BTreeSet;
 
 use crate::heading::Heading;
+use crate::kind::Kind;
 use crate::trace::Trace;
 
+use crate::rules::Collisions;
 use crate::world::{
-  World
-, WORLD_MAX_HEIGHT
+  Outlook
+, World
+, WORLD_LIMIT_CEILING
 , WORLD_MIN_HEIGHT
-, WORLD_MAX_WIDTH
 , WORLD_MIN_WIDTH
 };
 
 const LIB_NAME     : &str  = module_path!();
 const TRACE_ACTIVE : &bool = &false;
 
-const HEADINGS_LEFT :[Heading; 4] = [Heading::North, Heading::West, Heading::South, Heading::East];
-const HEADINGS_RIGHT:[Heading; 4] = [Heading::North, Heading::East, Heading::South, Heading::West];
+const PARSE_ERROR_MISSING_VALS  : &str = "Please specify the new robot's X Y location and its heading";
+const PARSE_ERROR_MISSING_Y_VAL : &str = "Expecting the robot's Y location and a heading, but found only its X location";
+const PARSE_ERROR_MISSING_HDNG  : &str = "Expecting the new robot's heading, but found only its X Y location";
+const PARSE_ERROR_BAD_X_VAL     : &str = "New robot's X location must be an integer";
+const PARSE_ERROR_BAD_Y_VAL     : &str = "New robot's Y location must be an integer";
 
-const PARSE_ERROR_MISSING_VALS  : &str = &"Please specify the new robot's X Y location and its heading";
-const PARSE_ERROR_MISSING_Y_VAL : &str = &"Expecting the robot's Y location and a heading, but found only its X location";
-const PARSE_ERROR_MISSING_HDNG  : &str = &"Expecting the new robot's heading, but found only its X Y location";
-const PARSE_ERROR_BAD_X_VAL     : &str = &"New robot's X location must be an integer";
-const PARSE_ERROR_BAD_Y_VAL     : &str = &"New robot's Y location must be an integer";
+const ERROR_OUTSIDE_WORLD_BOUNDS : &str = "Robot location lies outside permissible world boundaries";
+const ERROR_LOCATION_OCCUPIED    : &str = "Cannot create robot - location already occupied";
+const ERROR_LOCATION_BLOCKED     : &str = "Cannot create robot - location blocked by an obstacle";
+const ERROR_DUPLICATE_ID         : &str = "Cannot create robot - id already used in this world";
+const ERROR_DUPLICATE_NAME       : &str = "Cannot create robot - name already used in this world";
+const ERROR_NO_IDS_LEFT          : &str = "Cannot create robot - no ids left in this world, so please give it one";
 
-const ERROR_OUTSIDE_WORLD_BOUNDS : &str = &"Robot location lies outside permissible world boundaries";
+const PARSE_ERROR_BAD_ATTRIBUTE : &str = "Expecting robot attributes of the form name=<name>, id=<id>, type=<type>, energy=<energy> or cargo=<limit>";
+const PARSE_ERROR_BAD_ID        : &str = "Robot id must be a non-negative integer";
+const PARSE_ERROR_BAD_ENERGY    : &str = "Robot energy must be a non-negative integer";
+const PARSE_ERROR_BAD_CARGO     : &str = "Robot cargo limit must be a non-negative integer";
+const PARSE_ERROR_BAD_NAME      : &str = "Robot name must start with a letter and contain only letters, digits, '_' or '-'";
 
-pub const PROMPT_NEW_ROBOT : &str = &"Enter the zero-based location and heading for a new robot";
-pub const PROMPT_MOVE_TURN : &str = &"Enter move/turn instructions";
-pub const EOF_ENCOUNTERED  : &str = &"EOF stdin";
+pub const PROMPT_NEW_ROBOT : &str = "Enter the zero-based location and heading for a new robot";
+pub const PROMPT_MOVE_TURN : &str = "Enter move/turn instructions";
+
+pub const DEFAULT_CARGO_LIMIT : usize = 1;
+
+pub const ERROR_OUTSIDE_THIS_WORLD : &str = "Cannot create robot - location lies outside this world's boundaries";
+
+// *********************************************************************************************************************
+// Outcome of obeying a single move/turn command
+// *********************************************************************************************************************
+#[derive(Debug, PartialEq, Clone)]
+pub enum Step {
+  Turned              // Rotated on the spot
+, Waited              // Stayed put for a tick
+, PickedUp(String)    // Picked up this item from the current location
+, Dropped(String)     // Dropped this item at the current location
+, Overloaded          // Already carrying as many items as possible, so nothing was picked up
+, NoItem              // There was nothing to pick up or drop
+, Moved               // Moved forward
+, Pushed(i32)         // Moved forward, pushing the robot with this id one location further on
+, Blocked(i32, i32)   // Location (x,y) ahead is already occupied or is an obstacle, so the robot stayed put
+, Stopped(i32, i32)   // Moved forward part of the way, then found location (x,y) ahead occupied or an obstacle
+, GaveWay(i32)        // The robot with this id entered the location ahead earlier in the same tick, so the robot stayed put
+, Scented             // A previous robot was lost going this way, so the robot stayed put
+, Lost                // Fell off the edge of the world
+, Depleted            // Not enough energy left to obey the command
+, Invalid             // Not a move/turn command
+}
+
+impl Step {
+  // Did something in the way stop the robot, whether or not it had already moved?
+  pub fn is_blocked(&self) -> bool {
+    matches!(self, Step::Blocked(..) | Step::Stopped(..))
+  }
+}
 
 // *********************************************************************************************************************
 // Robot definition
 // *********************************************************************************************************************
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Robot {
-  pub id      : i32
-, pub x       : i32
-, pub y       : i32
-, pub heading : Heading
-, pub is_lost : bool
+  pub id          : i32
+, pub name        : Option<String>
+, pub x           : i32
+, pub y           : i32
+, pub heading     : Heading
+, pub kind        : Kind
+, pub is_lost     : bool
+, pub energy      : Option<u32>                 // None means the robot never runs out of energy
+, pub capacity    : Option<u32>                 // The most energy a charging station can refill the robot to
+, pub cargo       : Vec<String>                 // The items being carried, the most recently picked up last
+, pub cargo_limit : usize                       // The most items the robot can carry at once
+, pub visited     : BTreeSet<(i32, i32)>        // Every location the robot has been to
+, pub deliveries  : Vec<(String, i32, i32)>     // Every item the robot has dropped, and where
 }
 
+// Always in internal coordinates, so this is only for tests and debugging: anything a user sees goes through report()
 impl fmt::Display for Robot {
   fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-    write!(fmt, "{} {} {}{}", self.x, self.y, self.heading, if self.is_lost { " LOST" } else { "" })
+    match &self.name {
+      Some(name) => write!(fmt, "{}: {}", name, self.status())
+    , None       => write!(fmt, "{}", self.status())
+    }
   }
 }
 
@@ -53,84 +106,297 @@ impl fmt::Display for Robot {
 // Robot implementation
 // *********************************************************************************************************************
 impl Robot {
+  // Position, heading, whether the robot is lost or has run out of energy, what kind of robot it is (unless it's a
+  // plain rover) and what it's carrying
+  pub fn status(&self) -> String {
+    self.status_at(self.x, self.y)
+  }
+
+  // The same, but with the robot's position given in the world's own coordinate convention
+  pub fn status_in(&self, world : &World) -> String {
+    let (x, y) = world.to_external(&self.x, &self.y);
+    self.status_at(x, y)
+  }
+
+  // The robot's report as it appears in a world with its own coordinate convention
+  pub fn report(&self, world : &World) -> String {
+    match &self.name {
+      Some(name) => format!("{}: {}", name, self.status_in(world))
+    , None       => self.status_in(world)
+    }
+  }
+
+  fn status_at(&self, x : i32, y : i32) -> String {
+    let mut status = format!("{} {} {}", x, y, self.heading);
+
+    if self.is_lost {
+      status.push_str(" LOST");
+    }
+    else if self.is_depleted() {
+      status.push_str(" DEPLETED");
+    }
+
+    if self.kind != Kind::Rover {
+      status.push_str(&format!(" ({})", self.kind));
+    }
+
+    if !self.cargo.is_empty() {
+      status.push_str(&format!(" carrying {}", self.cargo.join(", ")));
+    }
+
+    status
+  }
+
+  pub fn is_depleted(&self) -> bool {
+    self.energy == Some(0)
+  }
+
+  // Out of energy with no charging station to wait on
+  pub fn is_stranded(&self, world : &World) -> bool {
+    self.is_depleted() && self.charger_here(world).is_none()
+  }
+
+  // The robot's name if it has one, otherwise its id
+  pub fn label(&self) -> String {
+    self.name.clone().unwrap_or_else(|| self.id.to_string())
+  }
+
+  // Does this robot answer to the given id or name?
+  pub fn is_called(&self, id_or_name : &str) -> bool {
+    self.id.to_string() == id_or_name || self.name.as_deref() == Some(id_or_name)
+  }
+
   pub fn turn_right(&mut self) {
-    self.heading = (*turn(&HEADINGS_RIGHT, &self.heading)).clone();
-    Trace::make_trace_fn(TRACE_ACTIVE, LIB_NAME, &"turn_right")(&format!("New heading = {}", &self.heading));
+    self.heading = self.heading.right();
+    Trace::make_trace_fn(TRACE_ACTIVE, LIB_NAME, "turn_right")(&format!("New heading = {}", &self.heading));
   }
 
   pub fn turn_left(&mut self) {
-    self.heading = (*turn(&HEADINGS_LEFT, &self.heading)).clone();
-    Trace::make_trace_fn(TRACE_ACTIVE, LIB_NAME, &"turn_left")(&format!("New heading = {}", &self.heading));
+    self.heading = self.heading.left();
+    Trace::make_trace_fn(TRACE_ACTIVE, LIB_NAME, "turn_left")(&format!("New heading = {}", &self.heading));
   }
 
   pub fn position(&mut self) -> (&i32, &i32) {
-    Trace::make_trace_fn(TRACE_ACTIVE, LIB_NAME, &"position")
+    Trace::make_trace_fn(TRACE_ACTIVE, LIB_NAME, "position")
                         (&format!("Robot {} at ({},{}) heading {}", &self.id, &self.x, &self.y, &self.heading));
     (&self.x, &self.y)
   }
 
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  // Sensors
+  //
+  // These only look at the world; they never change it
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  pub fn ahead(&self, world : &World) -> Outlook {
+    world.look(&self.x, &self.y, &self.heading)
+  }
+
+  // How much energy per tick does the charging station the robot is standing on give (if there is one)?
+  pub fn charger_here(&self, world : &World) -> Option<u32> {
+    world.charger_at(&self.x, &self.y)
+  }
+
+  pub fn charger_ahead(&self, world : &World) -> Option<u32> {
+    let (dx, dy) = self.heading.offset();
+    world.charger_at(&(self.x + dx), &(self.y + dy))
+  }
+
+  // How many steps forward can be taken before the next step would fall off the edge?
+  pub fn distance_to_edge(&self, world : &World) -> i32 {
+    match self.heading {
+      Heading::North => world.height - 1 - self.y
+    , Heading::East  => world.width  - 1 - self.x
+    , Heading::South => self.y
+    , Heading::West  => self.x
+    }
+  }
+
+  // The occupancy of the eight surrounding locations, clockwise from north (N, NE, E, SE, S, SW, W, NW)
+  pub fn neighbours(&self, world : &World) -> [Outlook; 8] {
+    const OFFSETS : [(i32, i32); 8] = [(0, 1), (1, 1), (1, 0), (1, -1), (0, -1), (-1, -1), (-1, 0), (-1, 1)];
+
+    OFFSETS.map(|(dx, dy)| world.occupant(&(self.x + dx), &(self.y + dy)))
+  }
+
   // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
   // Take a step forwards
+  //
+  // What that means depends on what kind of robot this is.  A hopper covers two locations, but stops short if anything
+  // gets in its way after the first
   // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
-  pub fn forward(&mut self, world : &mut World) {
-    const FN_NAME : &str = &"forward";
+  pub fn forward(&mut self, world : &mut World) -> Step {
+    const FN_NAME : &str = "forward";
 
     let trace_boundary = Trace::make_boundary_trace_fn(TRACE_ACTIVE, LIB_NAME, FN_NAME);
     let trace          = Trace::make_trace_fn(TRACE_ACTIVE, LIB_NAME, FN_NAME);
 
     trace_boundary(&Some(true));
 
-    // Check whether any previous robot has died by venturing in this direction from this location
-    if world.is_it_safe(&self.x, &self.y, &self.heading) {
-      trace(&format!("It appears safe to head {} from ({},{})", &self.heading, &self.x, &self.y));
+    let mut step = Step::Moved;
 
-      let (new_x, new_y) = match self.heading {
-        Heading::North => (self.x,     self.y + 1),
-        Heading::East  => (self.x + 1, self.y),
-        Heading::South => (self.x,     self.y - 1),
-        Heading::West  => (self.x - 1, self.y),
+    for hop in 0..self.kind.stride() {
+      step = match self.advance(world) {
+        // Having already covered some of the distance, the robot didn't stay put
+        Step::Blocked(x, y) if hop > 0 => Step::Stopped(x, y)
+      , step                           => step
       };
 
-      // Does the new location lie inside the world?
-      if (new_y < 0 || new_y >= world.height) ||
-         (new_x < 0 || new_x >= world.width) {
-        // Nope - KABOOM!
-        trace(&"Ouch! Just been eaten by monsters!");
-        // The robot is now lost so remove it from the world, warn other robots not to venture this way,
-        // but don't update its x and y values because its last known location needs to be printed
-        self.is_lost = true;
-        world.remove_robot_from(&self.x, &self.y);
-        world.here_be_monsters(&self.x, &self.y, &self.heading);
+      if step != Step::Moved {
+        break;
+      }
+    }
+
+    trace(&format!("Robot {} ({}) is now at {}", &self.id, &self.kind, &self.status()));
+    trace_boundary(&Some(false));
+    step
+  }
+
+  fn advance(&mut self, world : &mut World) -> Step {
+    let trace = Trace::make_trace_fn(TRACE_ACTIVE, LIB_NAME, "forward");
+
+    // Check whether any previous robot has died by venturing in this direction from this location.  Scouts don't care
+    if self.kind != Kind::Scout && !world.is_it_safe(&self.x, &self.y, &self.heading) {
+      trace(&format!("Ignoring instruction to head {} from ({},{}) - here be monsters!", &self.heading, &self.x, &self.y));
+      return Step::Scented
+    }
+
+    let (dx, dy)       = self.heading.offset();
+    let (new_x, new_y) = (self.x + dx, self.y + dy);
+
+    // Does the new location lie inside the world?
+    if (new_y < 0 || new_y >= world.height) ||
+       (new_x < 0 || new_x >= world.width) {
+      // Nope - KABOOM!
+      trace("Ouch! Just been eaten by monsters!");
+      // The robot is now lost so remove it from the world, warn other robots not to venture this way,
+      // but don't update its x and y values because its last known location needs to be printed
+      self.is_lost = true;
+      world.remove_robot_from(&self.x, &self.y);
+      world.here_be_monsters(&self.x, &self.y, &self.heading);
+      return Step::Lost
+    }
+
+    let mut step = Step::Moved;
+
+    // Nothing gets past an obstacle
+    if world.is_obstacle(&new_x, &new_y) {
+      trace(&format!("Can't go {} from ({},{}) - location ({},{}) blocked by an obstacle", &self.heading, &self.x, &self.y, &new_x, &new_y));
+      return Step::Blocked(new_x, new_y)
+    }
+
+    // Is the proposed location already occupied?
+    if let Outlook::Occupied(other_id) = world.occupant(&new_x, &new_y) {
+      let (beyond_x, beyond_y) = (new_x + dx, new_y + dy);
+
+      // Only a heavy robot can shove the other robot out of the way, and only into an empty location
+      if self.kind == Kind::Heavy && world.occupant(&beyond_x, &beyond_y) == Outlook::Free {
+        trace(&format!("Pushing robot {} from ({},{}) to ({},{})", &other_id, &new_x, &new_y, &beyond_x, &beyond_y));
+        world.remove_robot_from(&new_x, &new_y);
+        world.place_robot_at(&other_id, &beyond_x, &beyond_y);
+        step = Step::Pushed(other_id);
       }
       else {
-        // Is the proposed location already occupied?
-        if world.is_location_occupied(&new_x, &new_y) {
-          // Yup, so ignore this instruction
-          eprintln!("Can't go {} from ({},{}) - location already occupied!", &self.heading, &new_x, &new_y);
-        }
-        else {
-          // Nope, so update the robot's position and update the world grid
-          world.remove_robot_from(&self.x, &self.y);
-          world.place_robot_at(&self.id, &new_x, &new_y);
-          self.x = new_x;
-          self.y = new_y;
-          trace(&format!("Robot {} is now at ({},{}) heading {}", &self.id, &self.x, &self.y, &self.heading));
-        }
+        // Stuck, so ignore this instruction
+        trace(&format!("Can't go {} from ({},{}) - location ({},{}) already occupied", &self.heading, &self.x, &self.y, &new_x, &new_y));
+        return Step::Blocked(new_x, new_y)
+      }
+    }
+
+    // Update the robot's position and update the world grid
+    world.remove_robot_from(&self.x, &self.y);
+    world.place_robot_at(&self.id, &new_x, &new_y);
+    self.x = new_x;
+    self.y = new_y;
+    self.visited.insert((new_x, new_y));
+    step
+  }
+
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  // Obey a single move/turn command
+  //
+  // A command the robot can't afford drains whatever energy is left and is ignored.  Energy is only used up when the
+  // robot actually turns or moves.  Waiting is free, and is the only thing a robot without energy can still do
+  //
+  // Every command takes one tick, at the end of which a robot standing on a charging station is recharged
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  pub fn obey(&mut self, cmd : char, world : &mut World) -> Step {
+    let cost = match cmd {
+      'R' | 'L' => world.costs.turn
+    , 'F'       => world.costs.forward
+    , 'W'       => 0
+    , 'P' | 'D' => 0
+    , _         => return Step::Invalid
+    };
+
+    if let Some(energy) = self.energy {
+      if cmd != 'W' && (energy < cost || energy == 0) {
+        Trace::make_trace_fn(TRACE_ACTIVE, LIB_NAME, "obey")(&format!("Robot {} is out of energy", &self.id));
+        self.energy = Some(0);
+        self.recharge(world);
+        return Step::Depleted
       }
-    
+    }
+
+    let before = (self.x, self.y, self.heading.clone());
+
+    let step = match cmd {
+      'R' => { self.turn_right(); Step::Turned }
+    , 'L' => { self.turn_left();  Step::Turned }
+    , 'W' => Step::Waited
+    , 'P' => self.pick_up(world)
+    , 'D' => self.drop_off(world)
+    , _   => self.forward(world)
+    };
+
+    // A hopper that is blocked half way has still moved
+    if self.is_lost || before != (self.x, self.y, self.heading.clone()) {
+      self.energy = self.energy.map(|energy| energy - cost);
+    }
+
+    self.recharge(world);
+    step
+  }
+
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  // Cargo handling
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  pub fn pick_up(&mut self, world : &mut World) -> Step {
+    if world.items_at(&self.x, &self.y).is_empty() {
+      Step::NoItem
+    }
+    else if self.cargo.len() >= self.cargo_limit {
+      Step::Overloaded
     }
     else {
-      trace(&format!("Ignoring instruction to head {} from ({},{}) - here be monsters!", &self.heading, &self.x, &self.y));
+      let item = world.take_item(&self.x, &self.y).unwrap();
+      self.cargo.push(item.clone());
+      Step::PickedUp(item)
+    }
+  }
+
+  // Drop the most recently picked up item
+  pub fn drop_off(&mut self, world : &mut World) -> Step {
+    match self.cargo.pop() {
+      Some(item) => {
+        world.put_item(&self.x, &self.y, &item);
+        self.deliveries.push((item.clone(), self.x, self.y));
+        Step::Dropped(item)
+      }
+    , None => Step::NoItem
     }
+  }
 
-    trace_boundary(&Some(false));
+  fn recharge(&mut self, world : &World) {
+    if let (false, Some(energy), Some(capacity), Some(rate)) = (self.is_lost, self.energy, self.capacity, self.charger_here(world)) {
+      self.energy = Some(capacity.min(energy.saturating_add(rate)));
+    }
   }
 
   // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
   // Obey a set of move/turn instructions
   // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
-  pub fn turn_and_move(&mut self, line : &str, world : &mut World) {
-    const FN_NAME : &str = &"turn_and_move";
+  pub fn turn_and_move(&mut self, line : &str, world : &mut World) -> Vec<Step> {
+    const FN_NAME : &str = "turn_and_move";
 
     let trace_boundary = Trace::make_boundary_trace_fn(TRACE_ACTIVE, LIB_NAME, FN_NAME);
     let trace          = Trace::make_trace_fn(TRACE_ACTIVE, LIB_NAME, FN_NAME);
@@ -138,25 +404,36 @@ impl Robot {
     trace_boundary(&Some(true));
 
     let mut line_iter = line.split_ascii_whitespace();
+    let mut steps     = vec!();
 
-    for c in line_iter.next().unwrap().to_ascii_uppercase().chars() {
+    for c in line_iter.next().unwrap_or("").to_ascii_uppercase().chars() {
       // If I died as a result of following a previous instruction, then bail out
       if self.is_lost {
         break;
       }
       else {
         // Pass commands to robot
-        match c {
-          'R' => self.turn_right(),
-          'L' => self.turn_left(),
-          'F' => self.forward(world),
-          _z  => trace(&format!("Ignoring invalid move/turn command '{}'", _z))
+        let step = self.obey(c, world);
+
+        if step == Step::Invalid {
+          trace(&format!("Ignoring invalid move/turn command '{}'", c));
+        }
+
+        let halted = step.is_blocked() && world.rules.collisions == Collisions::Halt;
+
+        steps.push(step);
+
+        // Under this policy, a collision is the end of the line
+        if halted {
+          trace("Halting after a collision");
+          break;
         }
       }
     }
 
-    println!("{}", &self);
+    trace(&format!("Robot {} now at {}", &self.id, self.report(world)));
     trace_boundary(&Some(false));
+    steps
   }
 
   // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
@@ -165,66 +442,113 @@ impl Robot {
   pub fn new(id : i32, x : i32, y : i32, heading : Heading) -> Robot {
     Robot{
       id
+    , name : None
     , x
     , y
     , heading
-    , is_lost : false
+    , kind        : Kind::Rover
+    , is_lost     : false
+    , energy      : None
+    , capacity    : None
+    , cargo       : vec!()
+    , cargo_limit : DEFAULT_CARGO_LIMIT
+    , visited     : BTreeSet::from([(x, y)])
+    , deliveries  : vec!()
     }
   }
 }
 
 // *********************************************************************************************************************
-// Create a new robot from stdin data
+// Robot definitions start with the X coordinate; instructions never start with a digit
+// *********************************************************************************************************************
+pub fn is_robot_definition(line : &str) -> bool {
+  line.starts_with(|c : char| c.is_ascii_digit() || c == '-')
+}
+
+// *********************************************************************************************************************
+// Create a new robot from a robot definition and place it in the world
+// *********************************************************************************************************************
+pub fn create_robot(line : &str, world : &mut World) -> Result<Robot, &'static str> {
+  place_declared_robot(line.parse::<Robot>()?, world)
+}
+
+// *********************************************************************************************************************
+// Place a new robot whose location is given in the world's coordinate convention
+// *********************************************************************************************************************
+pub fn place_declared_robot(robot : Robot, world : &mut World) -> Result<Robot, &'static str> {
+  let (x, y) = world.to_internal(&robot.x, &robot.y);
+
+  place_robot(Robot { x, y, visited : BTreeSet::from([(x, y)]), ..robot }, world)
+}
+
+// *********************************************************************************************************************
+// Place a new robot in the world
 // *********************************************************************************************************************
-pub fn create_robot(line_arg : &str, world : &mut World, robot_id : &i32) -> Result<Robot, &'static str> {
-  const FN_NAME : &str = &"create_robot";
+pub fn place_robot(mut robot : Robot, world : &mut World) -> Result<Robot, &'static str> {
+  const FN_NAME : &str = "place_robot";
 
   let trace_boundary = Trace::make_boundary_trace_fn(TRACE_ACTIVE, LIB_NAME, FN_NAME);
   let trace          = Trace::make_trace_fn(TRACE_ACTIVE, LIB_NAME, FN_NAME);
 
   trace_boundary(&Some(true));
 
-  let mut stdin_data : Vec<u8> = Vec::new();
-  let mut stdin = BufReader::new(std::io::stdin());
-  let mut line  = line_arg.clone();
-
-  // Keep reading stdin until we get some valid robot data
-  loop {
-    // Parse stdin data to see if its a valid robot
-    match line.parse::<Robot>() {
-      Ok(mut robot) => {
-        // Check that new location is within the world's boundaries
-        if robot.x < world.width  &&
-           robot.y < world.height {
-          // Does the proposed location already contain a robot?
-          if world.is_location_occupied(&robot.x, &robot.y) {
-            eprintln!("ERROR: Cannot create robot at location ({},{}) - already occupied", robot.x, robot.y);
-          }
-          else {
-            // The robot's location is valid, so assign it the next id and place it at that world location
-            robot.id = *robot_id;
-            world.place_robot_at(&robot_id, &robot.x, &robot.y);
-            trace(&format!("New robot created at ({},{}) heading {}", robot.x, robot.y, robot.heading));
-            trace_boundary(&Some(false));
-            return Ok(robot)
-          }
-        }
-        else {
-          eprintln!("ERROR: Cannot create robot at location ({},{}) - outside world boundaries", robot.x, robot.y);
-        }
-     }
-    , Err(err_msg) => eprintln!("Error: {}", err_msg)
+  // Check that new location is within the world's boundaries
+  let result = if robot.x < 0 || robot.y < 0 || robot.x >= world.width || robot.y >= world.height {
+    Err(ERROR_OUTSIDE_THIS_WORLD)
+  }
+  else if world.is_obstacle(&robot.x, &robot.y) {
+    Err(ERROR_LOCATION_BLOCKED)
+  }
+  // Does the proposed location already contain a robot?
+  else if world.is_location_occupied(&robot.x, &robot.y) {
+    Err(ERROR_LOCATION_OCCUPIED)
+  }
+  else if robot.id < -1 {
+    Err(PARSE_ERROR_BAD_ID)
+  }
+  // Ids and names must be unique within a world
+  else if robot.id != -1 && world.has_robot_id(&robot.id) {
+    Err(ERROR_DUPLICATE_ID)
+  }
+  else if robot.name.as_ref().is_some_and(|name| world.has_robot_name(name)) {
+    Err(ERROR_DUPLICATE_NAME)
+  }
+  else if robot.name.as_ref().is_some_and(|name| !is_valid_name(name)) {
+    Err(PARSE_ERROR_BAD_NAME)
+  }
+  // The next id comes after the highest one used so far, which may already be the highest there is
+  else if robot.id == -1 && world.next_robot_id().is_none() {
+    Err(ERROR_NO_IDS_LEFT)
+  }
+  else {
+    // The robot's location is valid, so unless it asked for a particular id, assign it the next one and place it at
+    // that world location
+    if let (-1, Some(id)) = (robot.id, world.next_robot_id()) {
+      robot.id = id;
     }
 
-    // Wait for next line from stdin
-    stdin_data.clear();
-    prompt(PROMPT_NEW_ROBOT);
+    world.enrol_robot(&robot.id, &robot.name);
+    world.place_robot_at(&robot.id, &robot.x, &robot.y);
+    trace(&format!("New robot created at ({},{}) heading {}", robot.x, robot.y, robot.heading));
+    Ok(robot)
+  };
 
-    if stdin.read_until(b'\n', &mut stdin_data).unwrap() == 0 {
-      return Err(EOF_ENCOUNTERED);
-    }
+  trace_boundary(&Some(false));
+  result
+}
 
-    line = str::from_utf8(&stdin_data).unwrap().trim();
+// *********************************************************************************************************************
+// Bring the robots that a heavy robot pushed out of its way up to date with their new locations
+// *********************************************************************************************************************
+pub fn follow_pushes(robots : &mut [Robot], steps : &[Step], world : &World) {
+  for step in steps {
+    if let Step::Pushed(id) = step {
+      if let (Some(robot), Some((x, y))) = (robots.iter_mut().find(|r| r.id == *id), world.locate(id)) {
+        robot.x = x;
+        robot.y = y;
+        robot.visited.insert((x, y));
+      }
+    }
   }
 }
 
@@ -256,26 +580,65 @@ impl str::FromStr for Robot {
     };
 
     let h = match line_iter.next() {
-      Some(val) => match val.parse::<Heading>() {
-        Ok(hdg) => hdg
-      , Err(err_msg) => return Err(err_msg)
-      }
-    , None => return Err(PARSE_ERROR_MISSING_HDNG)
+      Some(val) => val.parse::<Heading>()?
+    , None      => return Err(PARSE_ERROR_MISSING_HDNG)
     };
 
+    // Optional attributes follow the heading
+    let mut id     : i32            = -1;
+    let mut name   : Option<String> = None;
+    let mut energy : Option<u32>    = None;
+    let mut kind   : Kind           = Kind::Rover;
+    let mut cargo  : usize          = DEFAULT_CARGO_LIMIT;
+
+    for attr in line_iter {
+      match attr.split_once('=') {
+        Some(("id", val)) => id = match val.parse::<i32>() {
+          Ok(int_val) if int_val >= 0 => int_val
+        , _                           => return Err(PARSE_ERROR_BAD_ID)
+        }
+      , Some(("type", val)) => kind = val.parse::<Kind>()?
+      , Some(("cargo", val)) => cargo = match val.parse::<usize>() {
+          Ok(int_val) => int_val
+        , Err(_)      => return Err(PARSE_ERROR_BAD_CARGO)
+        }
+      , Some(("energy", val)) => energy = match val.parse::<u32>() {
+          Ok(int_val) => Some(int_val)
+        , Err(_)      => return Err(PARSE_ERROR_BAD_ENERGY)
+        }
+      , Some(("name", val)) =>
+          if is_valid_name(val) {
+            name = Some(val.to_string())
+          }
+          else {
+            return Err(PARSE_ERROR_BAD_NAME)
+          }
+      , _ => return Err(PARSE_ERROR_BAD_ATTRIBUTE)
+      }
+    }
+
     // At this point in time, the only test we can perform on the robot's location is whether or not it falls within the
-    // maximum and minimum permissible world boundaries.
-    // Robot's (X,Y) location is zero-based, world dimensions are one-based
-    if x >= WORLD_MIN_WIDTH-1  && x < WORLD_MAX_WIDTH &&
-       y >= WORLD_MIN_HEIGHT-1 && y < WORLD_MAX_HEIGHT {
-      // The validity of the robot's location and its id are unknowable at this point in time
-      // The id will be assigned once the caller has validated the robot's location
+    // largest world that could ever be allowed.  The world it is placed in checks the rest
+    // Robot's (X,Y) location is zero- or one-based depending on the world's coordinate convention, world dimensions are
+    // one-based
+    if (WORLD_MIN_WIDTH-1  ..= WORLD_LIMIT_CEILING).contains(&x) &&
+       (WORLD_MIN_HEIGHT-1 ..= WORLD_LIMIT_CEILING).contains(&y) {
+      // The validity of the robot's location and the uniqueness of its id and name are unknowable at this point in time
+      // Unless one was given, the id will be assigned once the caller has validated the robot's location
       Ok(Robot {
-          id      : -1
-        , x       : x
-        , y       : y
+          id
+        , name
+        , x
+        , y
         , heading : h
-        , is_lost : false
+        , kind
+        , is_lost     : false
+        , energy
+        , capacity    : energy
+        , cargo       : vec!()
+        , cargo_limit : cargo
+        , visited     : BTreeSet::from([(x, y)])
+        , deliveries  : vec!()
         })
     }
     else {
@@ -287,14 +650,9 @@ impl str::FromStr for Robot {
 // *********************************************************************************************************************
 // Private API
 // *********************************************************************************************************************
-fn turn<'a>(headings : &'a [Heading; 4], hdg : &Heading) -> &'a Heading {
-  let idx = headings.iter().position(|h| h == hdg).unwrap();
-  &headings[(idx + 1) % 4]
-}
-
-fn prompt(prompt_msg : &str) {
-  print!("{} : ", prompt_msg);
-  let _ = std::io::stdout().flush();
+fn is_valid_name(name : &str) -> bool {
+  name.starts_with(|c : char| c.is_ascii_alphabetic()) &&
+  name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
 }
 
 // *********************************************************************************************************************
@@ -303,8 +661,10 @@ fn prompt(prompt_msg : &str) {
 #[cfg(test)]
 mod tests {
   use super::*;
-  use crate::world::Dimensions;
+  use crate::world::{Dimensions, EnergyCosts};
+  use crate::rules::ScentMode;
   use crate::heading::PARSE_ERROR_INVALID_HEADING;
+  use crate::kind::PARSE_ERROR_INVALID_KIND;
   
   #[test]
   fn spin_right() {
@@ -344,6 +704,187 @@ mod tests {
     test_bot.forward(&mut test_world);  assert!(test_bot.is_lost);
   }  
 
+  #[test]
+  fn place_robots() {
+    let mut test_world = World::new(&5, &3);
+
+    let robot = create_robot("1 1 e", &mut test_world).unwrap();
+    assert_eq!(robot.id, 0);
+    assert!(test_world.is_location_occupied(&1, &1));
+
+    let robot = create_robot("2 1 e id=7 name=scout", &mut test_world).unwrap();
+    assert_eq!((robot.id, robot.label()), (7, String::from("scout")));
+    assert_eq!(robot.to_string(), "scout: 2 1 E");
+    assert_eq!(create_robot("3 1 e", &mut test_world).unwrap().id, 8);
+
+    // Already occupied
+    let robot = create_robot("1 1 n", &mut test_world);
+    assert_eq!(robot.err(), Some(ERROR_LOCATION_OCCUPIED));
+
+    // Valid for some world, but not this one
+    let robot = create_robot("5 1 n", &mut test_world);
+    assert_eq!(robot.err(), Some(ERROR_OUTSIDE_THIS_WORLD));
+
+    // Ids and names must be unique
+    let robot = create_robot("0 0 n id=7", &mut test_world);
+    assert_eq!(robot.err(), Some(ERROR_DUPLICATE_ID));
+
+    let robot = create_robot("0 0 n name=scout", &mut test_world);
+    assert_eq!(robot.err(), Some(ERROR_DUPLICATE_NAME));
+
+    // Once the highest id has been used, the next robot must be given an id of its own
+    create_robot("0 2 n id=2147483647", &mut test_world).unwrap();
+    assert_eq!(create_robot("1 2 n", &mut test_world).err(), Some(ERROR_NO_IDS_LEFT));
+    assert_eq!(create_robot("1 2 n id=3", &mut test_world).map(|r| r.id), Ok(3));
+  }
+
+  #[test]
+  fn sensors() {
+    let mut test_world = World::new(&5, &3);
+
+    let robot = create_robot("0 1 n", &mut test_world).unwrap();
+    create_robot("1 2 s", &mut test_world).unwrap();
+
+    assert_eq!(robot.ahead(&test_world), Outlook::Free);
+    assert_eq!(robot.distance_to_edge(&test_world), 1);
+    assert_eq!( robot.neighbours(&test_world)
+              , [ Outlook::Free, Outlook::Occupied(1), Outlook::Free, Outlook::Free
+                , Outlook::Free, Outlook::Edge,        Outlook::Edge, Outlook::Edge
+                ]
+              );
+
+    test_world.here_be_monsters(&0, &1, &Heading::West);
+    let robot = Robot { heading : Heading::West, ..robot };
+    assert_eq!(robot.ahead(&test_world), Outlook::Scent);
+    assert_eq!(robot.distance_to_edge(&test_world), 0);
+  }
+
+  #[test]
+  fn run_out_of_energy() {
+    let mut test_world = World::new(&5, &5);
+    test_world.costs = EnergyCosts { forward : 2, turn : 1 };
+
+    let mut robot = create_robot("0 0 n energy=5", &mut test_world).unwrap();
+    assert_eq!(robot.turn_and_move("FRFL", &mut test_world), vec!(Step::Moved, Step::Turned, Step::Moved, Step::Depleted));
+    assert_eq!(robot.to_string(), "1 1 E DEPLETED");
+
+    // A command the robot can't afford drains the battery
+    let mut robot = create_robot("3 3 n energy=1", &mut test_world).unwrap();
+    assert_eq!(robot.obey('F', &mut test_world), Step::Depleted);
+    assert_eq!((robot.energy, robot.y), (Some(0), 3));
+
+    assert_eq!("0 0 n energy=x".parse::<Robot>().err(), Some(PARSE_ERROR_BAD_ENERGY));
+  }
+
+  #[test]
+  fn recharge() {
+    let mut test_world = World::new(&5, &5);
+    test_world.add_charger(&2, &0, 1);
+
+    let mut robot = create_robot("0 0 e energy=3", &mut test_world).unwrap();
+    assert_eq!(robot.charger_ahead(&test_world), None);
+
+    robot.turn_and_move("F", &mut test_world);
+    assert_eq!((robot.energy, robot.charger_ahead(&test_world)), (Some(2), Some(1)));
+
+    // Arriving on the charger takes a tick, at the end of which the robot is recharged
+    robot.turn_and_move("F", &mut test_world);
+    assert_eq!((robot.energy, robot.charger_here(&test_world)), (Some(2), Some(1)));
+
+    // Never charged beyond its capacity
+    robot.turn_and_move("WW", &mut test_world);
+    assert_eq!(robot.energy, Some(3));
+
+    // A command the robot can't afford still takes a tick, at the end of which it is recharged
+    robot.energy = Some(0);
+    assert!(!robot.is_stranded(&test_world));
+    assert_eq!(robot.turn_and_move("F", &mut test_world), vec!(Step::Depleted));
+    assert_eq!((robot.energy, robot.to_string()), (Some(1), String::from("2 0 E")));
+
+    // However much energy a robot can hold, charging it can't overflow
+    test_world.add_charger(&0, &1, 5);
+    let mut robot = create_robot("0 1 n energy=4294967295", &mut test_world).unwrap();
+    robot.turn_and_move("W", &mut test_world);
+    assert_eq!(robot.energy, Some(u32::MAX));
+  }
+
+  #[test]
+  fn robot_kinds() {
+    let mut test_world = World::new(&5, &3);
+
+    // A hopper covers two locations per step, unless something gets in the way
+    let mut hopper = create_robot("0 0 e type=hopper", &mut test_world).unwrap();
+    create_robot("4 0 n", &mut test_world).unwrap();
+
+    assert_eq!(hopper.forward(&mut test_world), Step::Moved);
+    assert_eq!(hopper.forward(&mut test_world), Step::Stopped(4, 0));
+    assert_eq!(hopper.forward(&mut test_world), Step::Blocked(4, 0));
+    assert_eq!(hopper.to_string(), "3 0 E (hopper)");
+
+    // A heavy robot pushes others out of its way, but only into an empty location
+    let mut heavy  = create_robot("0 2 e type=heavy", &mut test_world).unwrap();
+    let mut robots = vec!(create_robot("1 2 n", &mut test_world).unwrap());
+    create_robot("3 2 n", &mut test_world).unwrap();
+
+    let steps = heavy.turn_and_move("FF", &mut test_world);
+    assert_eq!(steps, vec!(Step::Pushed(robots[0].id), Step::Blocked(2, 2)));
+
+    follow_pushes(&mut robots, &steps, &test_world);
+    assert_eq!((heavy.x, robots[0].x), (1, 2));
+
+    // A scout ignores scents
+    test_world.here_be_monsters(&0, &1, &Heading::West);
+    let mut scout = create_robot("0 1 w type=scout", &mut test_world).unwrap();
+    assert_eq!(scout.forward(&mut test_world), Step::Lost);
+    assert_eq!(scout.to_string(), "0 1 W LOST (scout)");
+
+    assert_eq!("0 0 n type=tank".parse::<Robot>().err(), Some(PARSE_ERROR_INVALID_KIND));
+  }
+
+  #[test]
+  fn play_by_the_rules() {
+    // Under the halt policy, a robot ignores the rest of its instructions once it bumps into something
+    let mut test_world = World::new(&5, &3);
+    test_world.rules.collisions = Collisions::Halt;
+    test_world.add_obstacle(&2, &0);
+
+    let mut robot = create_robot("0 0 e", &mut test_world).unwrap();
+    assert_eq!(robot.turn_and_move("FFLF", &mut test_world), vec!(Step::Moved, Step::Blocked(2, 0)));
+    assert_eq!(robot.to_string(), "1 0 E");
+
+    // Without scents, every robot is free to make the same fatal mistake
+    test_world.rules.scents = ScentMode::Off;
+
+    for _ in 0..2 {
+      let mut robot = create_robot("4 2 n", &mut test_world).unwrap();
+      assert_eq!(robot.forward(&mut test_world), Step::Lost);
+    }
+
+    assert!(test_world.scents().is_empty());
+  }
+
+  #[test]
+  fn carry_cargo() {
+    let mut test_world = World::new(&5, &3);
+    test_world.put_item(&0, &0, "crate");
+    test_world.put_item(&0, &0, "box");
+
+    let mut robot = create_robot("0 0 n cargo=2", &mut test_world).unwrap();
+    assert_eq!( robot.turn_and_move("PPP", &mut test_world)
+              , vec!(Step::PickedUp(String::from("box")), Step::PickedUp(String::from("crate")), Step::NoItem)
+              );
+    assert_eq!(robot.to_string(), "0 0 N carrying box, crate");
+
+    // Drop the most recently picked up item first
+    assert_eq!(robot.turn_and_move("FD", &mut test_world), vec!(Step::Moved, Step::Dropped(String::from("crate"))));
+    assert_eq!(test_world.items(), vec!((0, 1, String::from("crate"))));
+
+    robot.cargo_limit = 1;
+    assert_eq!(robot.pick_up(&mut test_world), Step::Overloaded);
+
+    assert_eq!("0 0 n cargo=x".parse::<Robot>().err(), Some(PARSE_ERROR_BAD_CARGO));
+  }
+
   #[test]
   fn create_invalid_robots() {
     // Arguments missing
@@ -370,12 +911,22 @@ mod tests {
     let robot = "1 1 q".parse::<Robot>();
     assert_eq!(robot.err(), Some(PARSE_ERROR_INVALID_HEADING));
 
+    // Invalid attributes
+    let robot = "1 1 e scout".parse::<Robot>();
+    assert_eq!(robot.err(), Some(PARSE_ERROR_BAD_ATTRIBUTE));
+
+    let robot = "1 1 e id=-3".parse::<Robot>();
+    assert_eq!(robot.err(), Some(PARSE_ERROR_BAD_ID));
+
+    let robot = "1 1 e name=2nd".parse::<Robot>();
+    assert_eq!(robot.err(), Some(PARSE_ERROR_BAD_NAME));
+
     // Correct, but invalid location arguments
-    let robot = "0 0 e".parse::<Robot>();
+    let robot = "-1 -1 e".parse::<Robot>();
     assert_eq!(robot.err(), Some(ERROR_OUTSIDE_WORLD_BOUNDS));
 
     // Correct, but invalid location arguments
-    let robot = "51 51 e".parse::<Robot>();
+    let robot = format!("{} {} e", WORLD_LIMIT_CEILING + 1, WORLD_LIMIT_CEILING + 1).parse::<Robot>();
     assert_eq!(robot.err(), Some(ERROR_OUTSIDE_WORLD_BOUNDS));
   }  
 }
\ No newline at end of file
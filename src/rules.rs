@@ -0,0 +1,98 @@
+use std::{fmt, str};
+
+pub const PARSE_ERROR_BAD_COLLISIONS : &str = "Collision policy must be either block or halt";
+pub const PARSE_ERROR_BAD_SCENTS     : &str = "Scent mode must be either on or off";
+
+// *********************************************************************************************************************
+// What a robot does when an obstacle or another robot stops it moving forward
+// *********************************************************************************************************************
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum Collisions {
+  #[default]
+  Block       // Stay put, then carry on with the rest of the instructions
+, Halt        // Stay put, and ignore the rest of the instructions
+}
+
+impl fmt::Display for Collisions {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Collisions::Block => write!(f, "block")
+    , Collisions::Halt  => write!(f, "halt")
+    }
+  }
+}
+
+impl str::FromStr for Collisions {
+  type Err = &'static str;
+
+  fn from_str(s: &str) -> Result<Collisions, Self::Err> {
+    match s.trim().to_ascii_lowercase().as_str() {
+      "block" => Ok(Collisions::Block)
+    , "halt"  => Ok(Collisions::Halt)
+    , _       => Err(PARSE_ERROR_BAD_COLLISIONS)
+    }
+  }
+}
+
+// *********************************************************************************************************************
+// Whether lost robots leave a scent that warns others away
+// *********************************************************************************************************************
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum ScentMode {
+  #[default]
+  On          // As in the classic problem
+, Off         // No scent is left, so every robot is free to make the same fatal mistake
+}
+
+impl fmt::Display for ScentMode {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ScentMode::On  => write!(f, "on")
+    , ScentMode::Off => write!(f, "off")
+    }
+  }
+}
+
+impl str::FromStr for ScentMode {
+  type Err = &'static str;
+
+  fn from_str(s: &str) -> Result<ScentMode, Self::Err> {
+    match s.trim().to_ascii_lowercase().as_str() {
+      "on"  => Ok(ScentMode::On)
+    , "off" => Ok(ScentMode::Off)
+    , _     => Err(PARSE_ERROR_BAD_SCENTS)
+    }
+  }
+}
+
+// *********************************************************************************************************************
+// The rules a world is played by
+// *********************************************************************************************************************
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Rules {
+  pub collisions : Collisions
+, pub scents     : ScentMode
+}
+
+impl fmt::Display for Rules {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "collisions {}, scents {}", self.collisions, self.scents)
+  }
+}
+
+// *********************************************************************************************************************
+// Suppose we'd better test it...
+// *********************************************************************************************************************
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse_rules() {
+    assert_eq!("HALT".parse::<Collisions>(), Ok(Collisions::Halt));
+    assert_eq!("push".parse::<Collisions>(), Err(PARSE_ERROR_BAD_COLLISIONS));
+    assert_eq!(" off ".parse::<ScentMode>(), Ok(ScentMode::Off));
+    assert_eq!("maybe".parse::<ScentMode>(), Err(PARSE_ERROR_BAD_SCENTS));
+    assert_eq!(Rules::default().to_string(), "collisions block, scents on");
+  }
+}
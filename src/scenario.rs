@@ -0,0 +1,681 @@
+use std::{fmt, str};
+use std::path::PathBuf;
+
+use crate::coords::{Coords, PARSE_ERROR_BAD_COORDS};
+use crate::dry_run::DRY_RUN_PREFIX;
+use crate::input::SourceLine;
+use crate::kind::Kind;
+use crate::mission::Goal;
+use crate::program::{is_program, Program};
+use crate::robot::{is_robot_definition, Robot, DEFAULT_CARGO_LIMIT, ERROR_OUTSIDE_THIS_WORLD};
+use crate::scheduler::DIAG_PROGRAM_IN_LOCKSTEP;
+use crate::session::{SessionError, DEFAULT_WORLD_NAME, ERROR_BAD_SCHEDULING, ERROR_MISSING_WORLD_NAME, META_PREFIX};
+use crate::validate::{check_instructions, Diagnostic, Validation};
+use crate::world::{Dialect, Dimensions, Limits, PARSE_ERROR_BAD_DIALECT};
+use crate::trace::Trace;
+
+const LIB_NAME     : &str  = module_path!();
+const TRACE_ACTIVE : &bool = &false;
+
+pub const PARSE_ERROR_UNKNOWN_META : &str = "Unknown meta command";
+
+// *********************************************************************************************************************
+// Where something was found in the input
+// *********************************************************************************************************************
+#[derive(Debug, PartialEq, Clone)]
+pub struct Span {
+  pub path  : PathBuf
+, pub line  : usize     // 1-based
+, pub start : usize     // 1-based column of the first character
+, pub end   : usize     // 1-based column just past the last character
+}
+
+impl fmt::Display for Span {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}:{}:{}", self.path.display(), self.line, self.start)
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct Spanned<T> {
+  pub node : T
+, pub span : Span
+}
+
+// *********************************************************************************************************************
+// Scenario AST
+// *********************************************************************************************************************
+
+// A world's size, given either on the first line or by :new
+#[derive(Debug, PartialEq, Clone)]
+pub struct WorldDecl {
+  pub name    : Option<String>    // None for the first line, which creates the main world
+, pub dims    : Dimensions
+, pub dialect : Dialect           // How the size was written
+}
+
+// A new robot, with its location given in the world's coordinate convention
+#[derive(Debug, Clone)]
+pub struct RobotDecl {
+  pub robot : Robot
+}
+
+// A line of move/turn commands or a program
+#[derive(Debug, PartialEq, Clone)]
+pub struct Block {
+  pub text       : String
+, pub is_program : bool
+}
+
+// Every meta command but :new, which declares a world
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Meta {
+  Help
+, Show
+, Robots
+, Scents
+, Select
+, Undo
+, Reset
+, Obstacle
+, Charger
+, Item
+, Goal
+, Score
+, Schedule
+, Validation
+, Plans
+, Go
+, Coords
+, Dialect
+, Costs
+, Switch
+, Worlds
+, Script
+, Run
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct MetaCommand {
+  pub name : Meta
+, pub args : Vec<String>
+}
+
+#[derive(Debug, Clone)]
+pub enum Statement {
+  World(WorldDecl)
+, Robot(RobotDecl)
+, Instructions(Block)
+, DryRun(Block)
+, Meta(MetaCommand)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Scenario {
+  pub statements : Vec<Spanned<Statement>>
+}
+
+// *********************************************************************************************************************
+// Something wrong with the input, found before any of it was obeyed
+// *********************************************************************************************************************
+#[derive(Debug, PartialEq, Clone)]
+pub struct ParseError {
+  pub span    : Span
+, pub message : String
+}
+
+impl fmt::Display for ParseError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}: {}", self.span, self.message)
+  }
+}
+
+// *********************************************************************************************************************
+// Pretty printing
+//
+// Each statement is printed in a canonical form that the session treats exactly as it would the original line
+// *********************************************************************************************************************
+impl fmt::Display for WorldDecl {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let (w, h) = match self.dialect {
+      Dialect::Native  => (self.dims.width, self.dims.height)
+    , Dialect::Classic => (self.dims.width - 1, self.dims.height - 1)
+    };
+
+    match &self.name {
+      Some(name) => write!(f, "{}new {} {} {}", META_PREFIX, name, w, h)
+    , None       => write!(f, "{} {}", w, h)
+    }
+  }
+}
+
+impl fmt::Display for RobotDecl {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let robot = &self.robot;
+    write!(f, "{} {} {}", robot.x, robot.y, robot.heading)?;
+
+    if robot.id != -1                           { write!(f, " id={}", robot.id)?; }
+    if let Some(name) = &robot.name             { write!(f, " name={}", name)?; }
+    if robot.kind != Kind::Rover                { write!(f, " type={}", robot.kind)?; }
+    if let Some(energy) = robot.energy          { write!(f, " energy={}", energy)?; }
+    if robot.cargo_limit != DEFAULT_CARGO_LIMIT { write!(f, " cargo={}", robot.cargo_limit)?; }
+    Ok(())
+  }
+}
+
+impl fmt::Display for Meta {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let name = match self {
+      Meta::Help       => "help"
+    , Meta::Show       => "show"
+    , Meta::Robots     => "robots"
+    , Meta::Scents     => "scents"
+    , Meta::Select     => "select"
+    , Meta::Undo       => "undo"
+    , Meta::Reset      => "reset"
+    , Meta::Obstacle   => "obstacle"
+    , Meta::Charger    => "charger"
+    , Meta::Item       => "item"
+    , Meta::Goal       => "goal"
+    , Meta::Score      => "score"
+    , Meta::Schedule   => "schedule"
+    , Meta::Validation => "validation"
+    , Meta::Plans      => "plans"
+    , Meta::Go         => "go"
+    , Meta::Coords     => "coords"
+    , Meta::Dialect    => "dialect"
+    , Meta::Costs      => "costs"
+    , Meta::Switch     => "switch"
+    , Meta::Worlds     => "worlds"
+    , Meta::Script     => "script"
+    , Meta::Run        => "run"
+    };
+    write!(f, "{}", name)
+  }
+}
+
+impl str::FromStr for Meta {
+  type Err = &'static str;
+
+  fn from_str(s: &str) -> Result<Meta, Self::Err> {
+    match s.trim().to_ascii_lowercase().as_str() {
+      "help"       => Ok(Meta::Help)
+    , "show"       => Ok(Meta::Show)
+    , "robots"     => Ok(Meta::Robots)
+    , "scents"     => Ok(Meta::Scents)
+    , "select"     => Ok(Meta::Select)
+    , "undo"       => Ok(Meta::Undo)
+    , "reset"      => Ok(Meta::Reset)
+    , "obstacle"   => Ok(Meta::Obstacle)
+    , "charger"    => Ok(Meta::Charger)
+    , "item"       => Ok(Meta::Item)
+    , "goal"       => Ok(Meta::Goal)
+    , "score"      => Ok(Meta::Score)
+    , "schedule"   => Ok(Meta::Schedule)
+    , "validation" => Ok(Meta::Validation)
+    , "plans"      => Ok(Meta::Plans)
+    , "go"         => Ok(Meta::Go)
+    , "coords"     => Ok(Meta::Coords)
+    , "dialect"    => Ok(Meta::Dialect)
+    , "costs"      => Ok(Meta::Costs)
+    , "switch"     => Ok(Meta::Switch)
+    , "worlds"     => Ok(Meta::Worlds)
+    , "script"     => Ok(Meta::Script)
+    , "run"        => Ok(Meta::Run)
+    , _            => Err(PARSE_ERROR_UNKNOWN_META)
+    }
+  }
+}
+
+impl fmt::Display for MetaCommand {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}{}", META_PREFIX, self.name)?;
+
+    for arg in &self.args {
+      write!(f, " {}", arg)?;
+    }
+    Ok(())
+  }
+}
+
+impl fmt::Display for Statement {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Statement::World(decl)         => write!(f, "{}", decl)
+    , Statement::Robot(decl)         => write!(f, "{}", decl)
+    , Statement::Instructions(block) => write!(f, "{}", block.text.to_ascii_uppercase())
+    , Statement::DryRun(block)       => write!(f, "{}{}", DRY_RUN_PREFIX, block.text.to_ascii_uppercase())
+    , Statement::Meta(cmd)           => write!(f, "{}", cmd)
+    }
+  }
+}
+
+impl fmt::Display for Scenario {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    for statement in &self.statements {
+      writeln!(f, "{}", statement.node)?;
+    }
+    Ok(())
+  }
+}
+
+// *********************************************************************************************************************
+// Statement implementation
+// *********************************************************************************************************************
+impl Statement {
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  // Classify and parse one line of input
+  //
+  // Until there is a world, any line that isn't a meta command gives the main world's size; after that, lines starting
+  // with a digit define robots and everything else is instructions for the selected robot
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  pub fn parse(text : &str, has_world : bool, dialect : Dialect, limits : &Limits) -> Result<Statement, SessionError> {
+    let world = |name : Option<&str>, dims : &str| {
+      let dims = dialect.parse_dimensions(dims, limits).map_err(SessionError::InvalidDimensions)?;
+      Ok(Statement::World(WorldDecl { name : name.map(str::to_string), dims, dialect }))
+    };
+
+    if let Some(meta) = text.strip_prefix(META_PREFIX) {
+      let mut words = meta.split_ascii_whitespace();
+      let     name  = words.next().unwrap_or("");
+      let     args  = words.map(str::to_string).collect::<Vec<String>>();
+
+      if name.eq_ignore_ascii_case("new") {
+        return match args.split_first() {
+          Some((name, dims)) => world(Some(name), &dims.join(" "))
+        , None               => Err(SessionError::InvalidWorld(ERROR_MISSING_WORLD_NAME))
+        }
+      }
+
+      let name = name.parse::<Meta>().map_err(|_| SessionError::UnknownCommand(format!("{}{}", META_PREFIX, name)))?;
+      Ok(Statement::Meta(MetaCommand { name, args }))
+    }
+    else if !has_world {
+      world(None, text)
+    }
+    else if is_robot_definition(text) {
+      let robot = text.parse::<Robot>().map_err(SessionError::InvalidRobot)?;
+      Ok(Statement::Robot(RobotDecl { robot }))
+    }
+    else if let Some(cmds) = text.strip_prefix(DRY_RUN_PREFIX) {
+      Ok(Statement::DryRun(Block { text : cmds.to_string(), is_program : false }))
+    }
+    else {
+      Ok(Statement::Instructions(Block { text : text.to_string(), is_program : is_program(text) }))
+    }
+  }
+}
+
+// *********************************************************************************************************************
+// Scenario implementation
+// *********************************************************************************************************************
+impl Scenario {
+  pub fn worlds(&self) -> impl Iterator<Item = &WorldDecl> {
+    self.statements.iter().filter_map(|s| match &s.node { Statement::World(decl) => Some(decl), _ => None })
+  }
+
+  pub fn robots(&self) -> impl Iterator<Item = &RobotDecl> {
+    self.statements.iter().filter_map(|s| match &s.node { Statement::Robot(decl) => Some(decl), _ => None })
+  }
+
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  // Parse a whole input, reporting every problem that can be found without obeying any of it
+  //
+  // Each line is parsed into the very statement the session would run.  The worlds, their coordinate conventions and
+  // whether they have robots yet are followed from line to line, so that robots outside their world and instructions
+  // with no robot to obey them are caught too.  After an :undo, there's no telling what state the session is in, so
+  // from then on only the lines themselves are checked
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  pub fn parse(lines : &[SourceLine], dialect : Dialect, validation : Validation, limits : Limits) -> Result<Scenario, Vec<ParseError>> {
+    const FN_NAME : &str = "parse";
+
+    let trace_boundary = Trace::make_boundary_trace_fn(TRACE_ACTIVE, LIB_NAME, FN_NAME);
+    let trace          = Trace::make_trace_fn(TRACE_ACTIVE, LIB_NAME, FN_NAME);
+
+    trace_boundary(&Some(true));
+
+    let mut parser = Parser {
+      dialect
+    , validation
+    , limits
+    , coords   : Coords::default()
+    , lockstep : false
+    , worlds   : vec!()
+    , current  : None
+    , certain  : true
+    , errors   : vec!()
+    };
+
+    let mut scenario = Scenario::default();
+
+    for line in lines {
+      let span = Span { path : line.path.clone(), line : line.number, start : line.column, end : line.column + line.text.chars().count() };
+
+      if let Some(node) = parser.statement(&line.text, &span) {
+        scenario.statements.push(Spanned { node, span });
+      }
+    }
+
+    trace(&format!("{} statement(s), {} error(s)", scenario.statements.len(), parser.errors.len()));
+    trace_boundary(&Some(false));
+
+    if parser.errors.is_empty() {
+      Ok(scenario)
+    }
+    else {
+      Err(parser.errors)
+    }
+  }
+}
+
+// *********************************************************************************************************************
+// Private API
+// *********************************************************************************************************************
+
+// What is known about a world at this point in the input
+struct WorldFacts {
+  name      : String
+, dims      : Dimensions
+, coords    : Coords
+, has_robot : bool
+}
+
+struct Parser {
+  dialect    : Dialect
+, validation : Validation
+, limits     : Limits
+, coords     : Coords             // The coordinate convention for new worlds
+, lockstep   : bool
+, worlds     : Vec<WorldFacts>
+, current    : Option<usize>
+, certain    : bool               // False once an :undo has made the session's state unknowable
+, errors     : Vec<ParseError>
+}
+
+impl Parser {
+  fn statement(&mut self, text : &str, span : &Span) -> Option<Statement> {
+    let result = match Statement::parse(text, self.current.is_some(), self.dialect, &self.limits) {
+      Ok(statement) => self.check(statement)
+    , Err(err)      => Err(self.recover(text, err))
+    };
+
+    match result {
+      Ok(statement) => Some(statement)
+    , Err(errs)     => {
+        self.errors.extend(errs.into_iter().map(|(column, message)| ParseError {
+          span : Span { start : span.start + column - 1, ..span.clone() }
+        , message
+        }));
+        None
+      }
+    }
+  }
+
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  // Carry on past a line that couldn't be parsed, as if it had done what it was meant to
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  fn recover(&mut self, text : &str, err : SessionError) -> Vec<(usize, String)> {
+    match err {
+      SessionError::InvalidDimensions(_) => {
+        // So that the lines meant for the world are taken as such, but its size is unknown
+        let name = text.strip_prefix(META_PREFIX)
+                       .and_then(|meta| meta.split_ascii_whitespace().nth(1))
+                       .unwrap_or(DEFAULT_WORLD_NAME);
+
+        self.enter(name, Dimensions { width : 0, height : 0 });
+        self.certain = false;
+      }
+    , SessionError::InvalidRobot(_) => {
+        // Don't blame the following instructions for the lack of a robot
+        if let Some(idx) = self.current { self.worlds[idx].has_robot = true; }
+      }
+    , _ => ()
+    }
+
+    at_start(err)
+  }
+
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  // Statement checkers, following the state of the session and returning the 1-based column and message of each
+  // problem found
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  fn check(&mut self, statement : Statement) -> Result<Statement, Vec<(usize, String)>> {
+    match &statement {
+      Statement::World(decl)         => self.world(decl)?
+    , Statement::Robot(decl)         => self.robot(decl)?
+    , Statement::Instructions(block) => self.block(block)?
+    , Statement::DryRun(block)       => self.block(block).map_err(|errs| shift(errs, 1))?
+    , Statement::Meta(cmd)           => self.meta_command(cmd)?
+    }
+
+    Ok(statement)
+  }
+
+  fn world(&mut self, decl : &WorldDecl) -> Result<(), Vec<(usize, String)>> {
+    let name = decl.name.as_deref().unwrap_or(DEFAULT_WORLD_NAME);
+
+    if self.certain && self.worlds.iter().any(|w| w.name == name) {
+      return Err(at_start(SessionError::DuplicateWorld(name.to_string())))
+    }
+
+    self.enter(name, decl.dims);
+    Ok(())
+  }
+
+  fn robot(&mut self, decl : &RobotDecl) -> Result<(), Vec<(usize, String)>> {
+    let certain = self.certain;
+    let robot   = &decl.robot;
+
+    if let Some(world) = self.current.map(|idx| &mut self.worlds[idx]) {
+      let (x, y) = world.coords.internal(robot.x, robot.y, world.dims.height);
+
+      if certain && (x < 0 || y < 0 || x >= world.dims.width || y >= world.dims.height) {
+        return Err(at_start(SessionError::InvalidRobot(ERROR_OUTSIDE_THIS_WORLD)))
+      }
+
+      world.has_robot = true;
+    }
+
+    Ok(())
+  }
+
+  fn block(&mut self, block : &Block) -> Result<(), Vec<(usize, String)>> {
+    let mut errors = vec!();
+
+    if self.certain && !self.current.is_some_and(|idx| self.worlds[idx].has_robot) {
+      errors.extend(at_start(SessionError::NoRobotSelected));
+    }
+
+    if block.is_program && self.lockstep {
+      errors.push((1, DIAG_PROGRAM_IN_LOCKSTEP.to_string()));
+    }
+    else if block.is_program {
+      if let Err(diag) = Program::compile(&block.text) {
+        errors.push(describe(&diag));
+      }
+    }
+    else if self.validation == Validation::Strict {
+      errors.extend(check_instructions(&block.text).1.iter().map(describe));
+    }
+
+    if errors.is_empty() { Ok(()) } else { Err(errors) }
+  }
+
+  fn meta_command(&mut self, cmd : &MetaCommand) -> Result<(), Vec<(usize, String)>> {
+    let args = &cmd.args;
+    let spec = args.join(" ");
+
+    match cmd.name {
+      Meta::Goal if !args.is_empty() => {
+        self.needs_world()?;
+        spec.parse::<Goal>().map_err(|msg| at_start(SessionError::InvalidGoal(msg)))?;
+      }
+    , Meta::Select => {
+        self.needs_world()?;
+        if let Some(idx) = self.current { self.worlds[idx].has_robot = true; }
+      }
+    , Meta::Reset => {
+        self.needs_world()?;
+        match args.first().map(String::as_str) {
+          None | Some("scents") => if let Some(idx) = self.current { self.worlds[idx].has_robot = false; }
+        , Some(other)           => return Err(at_start(SessionError::UnknownCommand(format!("{}reset {}", META_PREFIX, other))))
+        }
+      }
+    , Meta::Switch => {
+        let target = args.first().map_or("", String::as_str);
+
+        match self.worlds.iter().position(|w| w.name == target) {
+          Some(idx)               => self.current = Some(idx)
+        , None if self.certain    => return Err(at_start(SessionError::UnknownWorld(target.to_string())))
+        , None                    => ()
+        }
+      }
+    , Meta::Schedule => match args.first().map(String::as_str) {
+        None               => ()
+      , Some("sequential") => self.lockstep = false
+      , Some("lockstep")   => self.lockstep = true
+      , Some(_)            => return Err(at_start(SessionError::InvalidWorld(ERROR_BAD_SCHEDULING)))
+      }
+    , Meta::Dialect => if let Some(dialect) = args.first() {
+        self.dialect = dialect.parse::<Dialect>().map_err(|_| at_start(SessionError::InvalidWorld(PARSE_ERROR_BAD_DIALECT)))?;
+      }
+    , Meta::Validation => if let Some(mode) = args.first() {
+        self.validation = mode.parse::<Validation>().map_err(|e| at_start(SessionError::InvalidWorld(e)))?;
+      }
+    , Meta::Coords if !args.is_empty() => {
+        self.coords = spec.parse::<Coords>().map_err(|_| at_start(SessionError::InvalidWorld(PARSE_ERROR_BAD_COORDS)))?;
+        if let Some(idx) = self.current { self.worlds[idx].coords = self.coords; }
+      }
+    , Meta::Undo => self.certain = false
+    , _          => ()
+    }
+
+    Ok(())
+  }
+
+  fn needs_world(&self) -> Result<(), Vec<(usize, String)>> {
+    if self.certain && self.current.is_none() { Err(at_start(SessionError::NoWorld)) } else { Ok(()) }
+  }
+
+  // Create a world and switch to it
+  fn enter(&mut self, name : &str, dims : Dimensions) {
+    self.worlds.push(WorldFacts { name : name.to_string(), dims, coords : self.coords, has_robot : false });
+    self.current = Some(self.worlds.len() - 1);
+  }
+}
+
+fn at_start(err : SessionError) -> Vec<(usize, String)> {
+  vec!((1, err.to_string()))
+}
+
+fn describe(diag : &Diagnostic) -> (usize, String) {
+  match diag.found {
+    Some(c) => (diag.column, format!("{} '{}'", diag.message, c))
+  , None    => (diag.column, diag.message.to_string())
+  }
+}
+
+// Make the columns of a dry run's instructions count from the start of the line, prefix and all
+fn shift(errors : Vec<(usize, String)>, by : usize) -> Vec<(usize, String)> {
+  errors.into_iter().map(|(column, message)| (column + by, message)).collect()
+}
+
+// *********************************************************************************************************************
+// Suppose we'd better test it...
+// *********************************************************************************************************************
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::path::Path;
+  use crate::input::read_lines;
+
+  fn parse(text : &str, dialect : Dialect) -> Result<Scenario, Vec<ParseError>> {
+    Scenario::parse(&read_lines(text, Path::new("test.txt")).unwrap(), dialect, Validation::Strict, Limits::DEFAULT)
+  }
+
+  fn messages(errors : Vec<ParseError>) -> Vec<String> {
+    errors.iter().map(ParseError::to_string).collect()
+  }
+
+  #[test]
+  fn parse_the_classic_sample() {
+    let scenario = parse("5 3\n1 1 E\nrfrfrfrf\n3 2 N id=7 name=scout type=hopper\n# lost\nFRRFLLFFRRFLL\n?ff\n", Dialect::Native).unwrap();
+
+    assert_eq!(scenario.statements.len(), 6);
+    assert_eq!(scenario.worlds().map(|w| w.dims).collect::<Vec<Dimensions>>(), vec!(Dimensions { width : 5, height : 3 }));
+    assert_eq!(scenario.robots().map(|r| r.robot.id).collect::<Vec<i32>>(), vec!(-1, 7));
+    assert_eq!(scenario.statements[5].span, Span { path : PathBuf::from("test.txt"), line : 7, start : 1, end : 4 });
+    assert!(matches!(scenario.statements[4].node, Statement::Instructions(Block { is_program : false, .. })));
+
+    // Columns count characters, not bytes
+    let errors = parse("5 3\n\u{3000}1 1 E name=Zoë\n", Dialect::Native).unwrap_err();
+    assert_eq!(errors[0].span, Span { path : PathBuf::from("test.txt"), line : 2, start : 2, end : 16 });
+
+    // Pretty printing gives the canonical form of every line
+    assert_eq!( scenario.to_string()
+              , "5 3\n1 1 E\nRFRFRFRF\n3 2 N id=7 name=scout type=hopper\nFRRFLLFFRRFLL\n?FF\n"
+              );
+  }
+
+  #[test]
+  fn parse_statements() {
+    let limits = Limits::DEFAULT;
+    let dims   = Dimensions { width : 4, height : 3 };
+
+    assert!(matches!(Statement::parse("3 2", false, Dialect::Classic, &limits), Ok(Statement::World(WorldDecl { name : None, dims : d, .. })) if d == dims));
+    assert!(matches!(Statement::parse(":new moon 4 3", true, Dialect::Native, &limits), Ok(Statement::World(WorldDecl { name : Some(_), .. }))));
+    assert!(matches!(Statement::parse("1 1 E", true, Dialect::Native, &limits), Ok(Statement::Robot(_))));
+    assert!(matches!(Statement::parse("REPEAT 2 { F }", true, Dialect::Native, &limits), Ok(Statement::Instructions(Block { is_program : true, .. }))));
+    assert!(matches!(Statement::parse("?FF", true, Dialect::Native, &limits), Ok(Statement::DryRun(Block { is_program : false, .. }))));
+    assert_eq!( Statement::parse(":SHOW", true, Dialect::Native, &limits).unwrap().to_string(), ":show");
+    assert_eq!( Statement::parse(":bogus", true, Dialect::Native, &limits).unwrap_err()
+              , SessionError::UnknownCommand(String::from(":bogus"))
+              );
+    assert_eq!( Statement::parse(":new", true, Dialect::Native, &limits).unwrap_err()
+              , SessionError::InvalidWorld(ERROR_MISSING_WORLD_NAME)
+              );
+  }
+
+  #[test]
+  fn parse_worlds_and_meta_commands() {
+    let scenario = parse("4 2\n:dialect classic\n:new big 9 9\n:coords 1 top-left\n5 5 N\n:switch main\n", Dialect::Native).unwrap();
+
+    assert_eq!( scenario.worlds().map(|w| (w.dims.width, w.dims.height)).collect::<Vec<(i32, i32)>>()
+              , vec!((4, 2), (10, 10))
+              );
+    assert_eq!(scenario.to_string(), "4 2\n:dialect classic\n:new big 9 9\n:coords 1 top-left\n5 5 N\n:switch main\n");
+  }
+
+  #[test]
+  fn report_every_problem() {
+    let errors = parse( "5 3\nF\n9 9 N\n0 0 Q\n1 1 E\nFXF\n?FFZ\n:bogus\nREPEAT 2 { F\n:switch moon\n:schedule lockstep\nREPEAT 2 { F }\n"
+                      , Dialect::Native
+                      ).unwrap_err();
+
+    assert_eq!( messages(errors)
+              , vec!( "test.txt:2:1: No robot selected - create one or use :select <id>"
+                    , "test.txt:3:1: Cannot create robot - location lies outside this world's boundaries"
+                    , "test.txt:4:1: Invalid heading"
+                    , "test.txt:6:2: Invalid move/turn command 'X'"
+                    , "test.txt:7:4: Invalid move/turn command 'Z'"
+                    , "test.txt:8:1: Unknown command ':bogus' - try :help"
+                    , "test.txt:9:13: Missing '}' at end of block"
+                    , "test.txt:10:1: No world called moon"
+                    , format!("test.txt:12:1: {}", DIAG_PROGRAM_IN_LOCKSTEP).as_str()
+                    )
+              );
+  }
+
+  #[test]
+  fn follow_the_dialect_and_coordinates() {
+    // (5,3) is the upper-right location in the classic dialect, but lies outside a native 5x3 world
+    assert!(parse("5 3\n5 3 N\n", Dialect::Classic).is_ok());
+    assert!(parse("5 3\n5 3 N\n", Dialect::Native).is_err());
+
+    // Counting from 1, (5,3) is the top-right location
+    assert!(parse(":coords 1 bottom-left\n5 3\n5 3 N\n", Dialect::Native).is_ok());
+
+    // After an :undo, the robot might well have been selected
+    assert!(parse("5 3\n:undo\nF\n", Dialect::Native).is_ok());
+  }
+}
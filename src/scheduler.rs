@@ -0,0 +1,342 @@
+use std::fmt;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::robot::{follow_pushes, Robot, Step};
+use crate::rules::Collisions;
+use crate::world::{Outlook, World};
+use crate::trace::Trace;
+
+const LIB_NAME     : &str  = module_path!();
+const TRACE_ACTIVE : &bool = &false;
+
+pub const DIAG_PROGRAM_IN_LOCKSTEP : &str = "Programs can't be scheduled in lockstep - use plain move/turn commands";
+
+// *********************************************************************************************************************
+// How instruction lines are carried out
+// *********************************************************************************************************************
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Scheduling {
+  Sequential    // Each robot obeys its whole instruction line as soon as it is given
+, Lockstep      // Instruction lines are queued, then all robots obey one command per tick
+}
+
+impl fmt::Display for Scheduling {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Scheduling::Sequential => write!(f, "sequential")
+    , Scheduling::Lockstep   => write!(f, "lockstep")
+    }
+  }
+}
+
+// *********************************************************************************************************************
+// Two robots tried to enter the same location on the same tick
+// *********************************************************************************************************************
+#[derive(Debug, PartialEq, Clone)]
+pub struct Conflict {
+  pub tick   : u32          // Counting from 1
+, pub robot  : i32          // The robot that had to stay put
+, pub winner : i32          // The robot that got there first
+, pub x      : i32
+, pub y      : i32
+}
+
+// *********************************************************************************************************************
+// Robots that were each blocked by the next one round, with the last blocked by the first
+// *********************************************************************************************************************
+#[derive(Debug, PartialEq, Clone)]
+pub struct Deadlock {
+  pub tick   : u32
+, pub robots : Vec<i32>     // In ascending order of id
+}
+
+// *********************************************************************************************************************
+// The robots ended up exactly as they were at an earlier tick, having got in each other's way since then
+// *********************************************************************************************************************
+#[derive(Debug, PartialEq, Clone)]
+pub struct Livelock {
+  pub tick   : u32
+, pub since  : u32          // The earlier tick (0 being the state before the first tick)
+, pub robots : Vec<i32>     // The robots that were blocked or gave way in between
+}
+
+// *********************************************************************************************************************
+// The outcome of running every robot's plan
+// *********************************************************************************************************************
+#[derive(Debug, PartialEq, Default)]
+pub struct Schedule {
+  pub ticks     : u32
+, pub steps     : BTreeMap<i32, Vec<Step>>    // What each robot did, tick by tick
+, pub conflicts : Vec<Conflict>
+, pub deadlocks : Vec<Deadlock>                // Each set of robots is reported only at the first tick it locks up
+, pub livelock  : Option<Livelock>             // Only the first repeated state is reported
+}
+
+// *********************************************************************************************************************
+// Run each robot's planned commands in lockstep, one command per robot per tick
+//
+// Within a tick, robots act in ascending order of id, so a robot may move into a location vacated earlier in the same
+// tick, but not into one still occupied by a robot that has yet to act.  When a robot's forward step would take it into
+// (or through) a location that another robot has already entered during this tick, the robot that got there first
+// keeps it and the other gives way by staying put.  Either way, the command is used up
+//
+// After every tick, the robots that blocked each other in a cycle are reported as deadlocked, and the first return to an
+// earlier state of all the robots is reported as a livelock, provided someone was blocked or gave way in the meantime
+// (a robot simply spinning on the spot is not a livelock, and nor are robots standing still)
+// *********************************************************************************************************************
+pub fn run_lockstep(robots : &mut [Robot], plans : &BTreeMap<i32, String>, world : &mut World) -> Schedule {
+  const FN_NAME : &str = "run_lockstep";
+
+  let trace_boundary = Trace::make_boundary_trace_fn(TRACE_ACTIVE, LIB_NAME, FN_NAME);
+  let trace          = Trace::make_trace_fn(TRACE_ACTIVE, LIB_NAME, FN_NAME);
+
+  trace_boundary(&Some(true));
+
+  let plans : BTreeMap<i32, Vec<char>> = plans.iter().map(|(id, cmds)| (*id, cmds.chars().collect())).collect();
+  let ticks = plans.values().map(|cmds| cmds.len()).max().unwrap_or(0) as u32;
+
+  let mut order : Vec<usize> = (0..robots.len()).collect();
+  order.sort_by_key(|idx| robots[*idx].id);
+
+  let mut schedule = Schedule { ticks, ..Schedule::default() };
+
+  // When each state of all the robots was first seen, and who got in someone's way on each tick (entry 0 being the
+  // initial state)
+  let mut previous   : Vec<String>                = snapshot(robots);
+  let mut seen       : BTreeMap<Vec<String>, u32> = BTreeMap::from([(previous.clone(), 0)]);
+  let mut obstructed : Vec<BTreeSet<i32>>         = vec!(BTreeSet::new());
+  // Robots that have stopped obeying their plans after a collision
+  let mut halted     : BTreeSet<i32>              = BTreeSet::new();
+
+  for tick in 0..ticks {
+    // Locations entered during this tick, and by whom
+    let mut claimed  : BTreeMap<(i32, i32), i32> = BTreeMap::new();
+    // Which robot was blocked by which during this tick
+    let mut blockers : BTreeMap<i32, i32>        = BTreeMap::new();
+
+    obstructed.push(BTreeSet::new());
+
+    for idx in &order {
+      let id = robots[*idx].id;
+
+      let cmd = match plans.get(&id).and_then(|cmds| cmds.get(tick as usize)) {
+        Some(cmd) if !robots[*idx].is_lost && !halted.contains(&id) => *cmd
+      , _                                                           => continue
+      };
+
+      let robot = &mut robots[*idx];
+      let (dx, dy) = robot.heading.offset();
+
+      let contested = if cmd == 'F' {
+        (1..=robot.kind.stride() as i32)
+          .map(|n| (robot.x + dx * n, robot.y + dy * n))
+          .find_map(|cell| claimed.get(&cell).map(|winner| (cell, *winner)))
+      }
+      else {
+        None
+      };
+
+      let step = match contested {
+        Some(((x, y), winner)) => {
+          trace(&format!("Tick {}: robot {} gives way to robot {} at ({},{})", tick + 1, id, winner, x, y));
+          schedule.conflicts.push(Conflict { tick : tick + 1, robot : id, winner, x, y });
+          Step::GaveWay(winner)
+        }
+      , None => {
+          let before = (robot.x, robot.y);
+          let step   = robot.obey(cmd, world);
+
+          if !robot.is_lost && before != (robot.x, robot.y) {
+            claimed.insert((robot.x, robot.y), id);
+          }
+
+          step
+        }
+      };
+
+      // Anyone pushed out of the way has entered a new location too
+      if let Step::Pushed(other) = step {
+        if let Some(cell) = world.locate(&other) {
+          claimed.insert(cell, id);
+        }
+
+        follow_pushes(robots, std::slice::from_ref(&step), world);
+      }
+
+      if step.is_blocked() && world.rules.collisions == Collisions::Halt {
+        halted.insert(id);
+      }
+
+      let in_the_way = match step {
+        Step::Blocked(x, y) => match world.occupant(&x, &y) {
+          Outlook::Occupied(other) => { blockers.insert(id, other); Some(other) }
+        , _                        => None
+        }
+      , Step::GaveWay(other) => Some(other)
+      , _                    => None
+      };
+
+      if let Some(other) = in_the_way {
+        obstructed.last_mut().unwrap().extend([id, other]);
+      }
+
+      schedule.steps.entry(id).or_default().push(step);
+    }
+
+    let tick = tick + 1;
+
+    for robots in blocking_cycles(&blockers) {
+      if !schedule.deadlocks.iter().any(|d| d.robots == robots) {
+        trace(&format!("Tick {}: deadlock between robots {:?}", tick, robots));
+        schedule.deadlocks.push(Deadlock { tick, robots });
+      }
+    }
+
+    let state = snapshot(robots);
+
+    match seen.get(&state) {
+      Some(_) if state == previous               => ()
+    , Some(since) if schedule.livelock.is_none() => {
+        let involved : BTreeSet<i32> = obstructed[(*since as usize + 1)..].iter().flatten().copied().collect();
+
+        if !involved.is_empty() {
+          trace(&format!("Tick {}: livelock - back to the state at tick {}", tick, since));
+          schedule.livelock = Some(Livelock { tick, since : *since, robots : involved.into_iter().collect() });
+        }
+      }
+    , Some(_) => ()
+    , None    => { seen.insert(state.clone(), tick); }
+    }
+
+    previous = state;
+  }
+
+  trace_boundary(&Some(false));
+  schedule
+}
+
+// *********************************************************************************************************************
+// Private API
+// *********************************************************************************************************************
+
+// Everything about the robots that matters for deciding whether they are going round in circles
+fn snapshot(robots : &[Robot]) -> Vec<String> {
+  let mut state : Vec<String> = robots.iter().map(|r| format!("{} {}", r.id, r.status())).collect();
+  state.sort();
+  state
+}
+
+// Each robot is blocked by at most one other, so following the chain from any robot either runs out or goes round a loop
+fn blocking_cycles(blockers : &BTreeMap<i32, i32>) -> Vec<Vec<i32>> {
+  let mut cycles : Vec<Vec<i32>> = vec!();
+
+  for start in blockers.keys() {
+    let mut chain = vec!(*start);
+
+    while let Some(next) = blockers.get(chain.last().unwrap()) {
+      if let Some(pos) = chain.iter().position(|id| id == next) {
+        let mut cycle = chain.split_off(pos);
+        cycle.sort();
+
+        if !cycles.contains(&cycle) {
+          cycles.push(cycle);
+        }
+        break
+      }
+
+      chain.push(*next);
+    }
+  }
+
+  cycles
+}
+
+// *********************************************************************************************************************
+// Suppose we'd better test it...
+// *********************************************************************************************************************
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::robot::create_robot;
+
+  fn plans(cmds : &[(i32, &str)]) -> BTreeMap<i32, String> {
+    cmds.iter().map(|(id, line)| (*id, line.to_string())).collect()
+  }
+
+  #[test]
+  fn lower_id_wins() {
+    let mut world  = World::new(&5, &3);
+    let mut robots = vec!( create_robot("2 0 n", &mut world).unwrap()
+                         , create_robot("0 1 e", &mut world).unwrap()
+                         );
+
+    // Both robots head for (2,1) on the second tick
+    let schedule = run_lockstep(&mut robots, &plans(&[(0, "WF"), (1, "FF")]), &mut world);
+
+    assert_eq!(schedule.ticks, 2);
+    assert_eq!(schedule.conflicts, vec!(Conflict { tick : 2, robot : 1, winner : 0, x : 2, y : 1 }));
+    assert_eq!(schedule.steps[&1], vec!(Step::Moved, Step::GaveWay(0)));
+    assert_eq!((robots[0].to_string(), robots[1].to_string()), (String::from("2 1 N"), String::from("1 1 E")));
+  }
+
+  #[test]
+  fn follow_the_leader() {
+    let mut world  = World::new(&5, &3);
+    let mut robots = vec!( create_robot("2 0 e", &mut world).unwrap()
+                         , create_robot("1 0 e", &mut world).unwrap()
+                         );
+
+    // Robot 0 moves first, so robot 1 can take its place on the same tick
+    let schedule = run_lockstep(&mut robots, &plans(&[(0, "FF"), (1, "FF")]), &mut world);
+
+    assert!(schedule.conflicts.is_empty());
+    assert_eq!((robots[0].x, robots[1].x), (4, 3));
+
+    // ...but not the other way round
+    let mut world  = World::new(&5, &3);
+    let mut robots = vec!( create_robot("1 0 e", &mut world).unwrap()
+                         , create_robot("2 0 e", &mut world).unwrap()
+                         );
+
+    let schedule = run_lockstep(&mut robots, &plans(&[(0, "F"), (1, "F")]), &mut world);
+    assert_eq!(schedule.steps[&0], vec!(Step::Blocked(2, 0)));
+    assert_eq!((robots[0].x, robots[1].x), (1, 3));
+  }
+
+  #[test]
+  fn deadlock() {
+    let mut world  = World::new(&5, &5);
+    let mut robots = vec!( create_robot("1 1 e", &mut world).unwrap()
+                         , create_robot("2 1 n", &mut world).unwrap()
+                         , create_robot("2 2 w", &mut world).unwrap()
+                         , create_robot("1 2 s", &mut world).unwrap()
+                         , create_robot("4 4 s", &mut world).unwrap()
+                         );
+
+    // Four robots going round in a square each wait for the one ahead to move, while robot 4 is nowhere near
+    let schedule = run_lockstep(&mut robots, &plans(&[(0, "FF"), (1, "FF"), (2, "FF"), (3, "FF"), (4, "F")]), &mut world);
+
+    assert_eq!(schedule.deadlocks, vec!(Deadlock { tick : 1, robots : vec!(0, 1, 2, 3) }));
+    assert_eq!(schedule.livelock, None);
+  }
+
+  #[test]
+  fn livelock() {
+    let mut world  = World::new(&5, &3);
+    let mut robots = vec!( create_robot("1 1 e", &mut world).unwrap()
+                         , create_robot("3 1 w", &mut world).unwrap()
+                         );
+
+    // Both robots go for (2,1), then robot 0 backs off and they both turn round to try again
+    let schedule = run_lockstep(&mut robots, &plans(&[(0, "FRRFLL"), (1, "FLLWRR")]), &mut world);
+
+    assert!(schedule.deadlocks.is_empty());
+    assert_eq!(schedule.livelock, Some(Livelock { tick : 6, since : 0, robots : vec!(0, 1) }));
+
+    // Spinning on the spot is fine
+    let mut world  = World::new(&5, &3);
+    let mut robots = vec!(create_robot("1 1 e", &mut world).unwrap());
+
+    assert_eq!(run_lockstep(&mut robots, &plans(&[(0, "RRRR")]), &mut world).livelock, None);
+  }
+}
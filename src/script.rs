@@ -0,0 +1,233 @@
+use std::fs;
+
+use rhai::{Dynamic, Engine, Map, Scope, AST};
+
+use crate::heading::Heading;
+use crate::robot::{Robot, Step};
+use crate::rules::Collisions;
+use crate::validate::VALID_COMMANDS;
+use crate::world::{Outlook, World};
+use crate::trace::Trace;
+
+const LIB_NAME     : &str  = module_path!();
+const TRACE_ACTIVE : &bool = &false;
+
+pub const SCRIPT_FN_NAME   : &str = "next_command";
+pub const MAX_SCRIPT_TICKS : u32  = 100;
+
+// Stop runaway scripts from hanging the simulator within a single tick
+const MAX_SCRIPT_OPERATIONS : u64 = 100_000;
+
+const LOOK_FREE     : &str = "free";
+const LOOK_EDGE     : &str = "edge";
+const LOOK_SCENT    : &str = "scent";
+const LOOK_OCCUPIED : &str = "occupied";
+const LOOK_OBSTACLE : &str = "obstacle";
+
+// *********************************************************************************************************************
+// A compiled Rhai script that decides what a robot does next
+//
+// The script must define a function next_command(robot) that is called once per tick and returns the next move/turn
+// command as a string, or an empty string to stop.  The robot argument is a map containing:
+//
+//   x, y, heading, tick                 The robot's position (in the world's coordinates), heading and the zero-based tick
+//                                       number
+//   type                                "rover", "hopper", "heavy" or "scout"
+//   ahead, left, right, behind          "free", "edge", "scent", "occupied" or "obstacle"
+//   distance                            The number of steps that can be taken before reaching the edge
+//   energy                              The energy left, or -1 if the robot never runs out
+//   charger                             The energy per tick given by a charging station here, otherwise 0
+//   items, cargo                        The number of items lying here, and the number being carried
+// *********************************************************************************************************************
+#[derive(Debug, Clone)]
+pub struct RobotScript {
+  pub name : String
+, ast      : AST
+}
+
+// *********************************************************************************************************************
+// The outcome of running a script
+// *********************************************************************************************************************
+#[derive(Debug, PartialEq)]
+pub struct ScriptRun {
+  pub ticks : u32
+, pub steps : Vec<Step>
+}
+
+// *********************************************************************************************************************
+// RobotScript implementation
+// *********************************************************************************************************************
+impl RobotScript {
+  pub fn compile(name : &str, source : &str) -> Result<RobotScript, String> {
+    let ast = new_engine().compile(source).map_err(|e| format!("{}: {}", name, e))?;
+
+    if !ast.iter_functions().any(|f| f.name == SCRIPT_FN_NAME && f.params.len() == 1) {
+      return Err(format!("{}: Script must define the function {}(robot)", name, SCRIPT_FN_NAME))
+    }
+
+    Ok(RobotScript { name : name.to_string(), ast })
+  }
+
+  pub fn load(path : &str) -> Result<RobotScript, String> {
+    let source = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    RobotScript::compile(path, &source)
+  }
+
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  // Let the script drive the robot until it stops, the robot is lost, stranded without energy or halted by a collision,
+  // or we run out of ticks
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  pub fn run(&self, robot : &mut Robot, world : &mut World, max_ticks : u32) -> Result<ScriptRun, String> {
+    const FN_NAME : &str = "run";
+
+    let trace_boundary = Trace::make_boundary_trace_fn(TRACE_ACTIVE, LIB_NAME, FN_NAME);
+    let trace          = Trace::make_trace_fn(TRACE_ACTIVE, LIB_NAME, FN_NAME);
+
+    trace_boundary(&Some(true));
+
+    let     engine = new_engine();
+    let mut scope  = Scope::new();
+    let mut result = ScriptRun { ticks : 0, steps : vec!() };
+
+    while result.ticks < max_ticks && !robot.is_lost && !robot.is_stranded(world) {
+      let view  = surroundings(robot, world, result.ticks);
+      let reply = engine.call_fn::<Dynamic>(&mut scope, &self.ast, SCRIPT_FN_NAME, (view,))
+                        .map_err(|e| format!("{}: tick {}: {}", self.name, result.ticks, e))?;
+
+      let cmd = match reply.into_string() {
+        Ok(cmd)        => cmd.trim().to_ascii_uppercase()
+      , Err(type_name) =>
+          return Err(format!("{}: tick {}: {}() returned {} instead of a string", self.name, result.ticks, SCRIPT_FN_NAME, type_name))
+      };
+
+      trace(&format!("Tick {}: robot {} at {} obeys '{}'", result.ticks, robot.label(), robot.status(), cmd));
+
+      let mut chars = cmd.chars();
+
+      match (chars.next(), chars.next()) {
+        (None, _)                                      => break
+      , (Some(c), None) if VALID_COMMANDS.contains(&c) => result.steps.push(robot.obey(c, world))
+      , _ => return Err(format!("{}: tick {}: invalid move/turn command '{}'", self.name, result.ticks, cmd))
+      }
+
+      result.ticks += 1;
+
+      // Under this policy, a collision is the end of the line for a script too
+      if world.rules.collisions == Collisions::Halt && result.steps.last().is_some_and(Step::is_blocked) {
+        trace("Halting after a collision");
+        break;
+      }
+    }
+
+    trace_boundary(&Some(false));
+    Ok(result)
+  }
+}
+
+// *********************************************************************************************************************
+// Private API
+// *********************************************************************************************************************
+fn new_engine() -> Engine {
+  let mut engine = Engine::new();
+  engine.set_max_operations(MAX_SCRIPT_OPERATIONS);
+  engine
+}
+
+// What the robot can see around it
+fn surroundings(robot : &Robot, world : &World, tick : u32) -> Map {
+  let mut view = Map::new();
+
+  let (x, y) = world.to_external(&robot.x, &robot.y);
+
+  view.insert("x".into(),       Dynamic::from(x as i64));
+  view.insert("y".into(),       Dynamic::from(y as i64));
+  view.insert("heading".into(), Dynamic::from(robot.heading.to_string()));
+  view.insert("tick".into(),    Dynamic::from(tick as i64));
+  view.insert("type".into(),    Dynamic::from(robot.kind.to_string()));
+  view.insert("distance".into(), Dynamic::from(robot.distance_to_edge(world) as i64));
+  view.insert("energy".into(),   Dynamic::from(robot.energy.map_or(-1, |e| e as i64)));
+  view.insert("charger".into(),  Dynamic::from(robot.charger_here(world).unwrap_or(0) as i64));
+  view.insert("items".into(),    Dynamic::from(world.items_at(&robot.x, &robot.y).len() as i64));
+  view.insert("cargo".into(),    Dynamic::from(robot.cargo.len() as i64));
+  view.insert("ahead".into(),   Dynamic::from(look(robot, world, &robot.heading)));
+  view.insert("left".into(),    Dynamic::from(look(robot, world, &robot.heading.left())));
+  view.insert("right".into(),   Dynamic::from(look(robot, world, &robot.heading.right())));
+  view.insert("behind".into(),  Dynamic::from(look(robot, world, &robot.heading.left().left())));
+
+  view
+}
+
+// What lies one step away in the given direction?
+fn look(robot : &Robot, world : &World, heading : &Heading) -> &'static str {
+  match world.look(&robot.x, &robot.y, heading) {
+    Outlook::Free        => LOOK_FREE
+  , Outlook::Occupied(_) => LOOK_OCCUPIED
+  , Outlook::Edge        => LOOK_EDGE
+  , Outlook::Scent       => LOOK_SCENT
+  , Outlook::Obstacle    => LOOK_OBSTACLE
+  }
+}
+
+// *********************************************************************************************************************
+// Suppose we'd better test it...
+// *********************************************************************************************************************
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // Walk forward until something is in the way, then turn right, giving up after 6 ticks
+  const WALKER : &str = r#"
+    fn next_command(robot) {
+      if robot.tick >= 6            { "" }
+      else if robot.ahead == "free" { "F" }
+      else                          { "R" }
+    }
+  "#;
+
+  #[test]
+  fn script_drives_robot() {
+    let mut world  = World::new(&3, &3);
+    let mut robot  = Robot::new(0, 0, 0, Heading::North);
+    let     script = RobotScript::compile("walker", WALKER).unwrap();
+
+    world.place_robot_at(&robot.id, &robot.x, &robot.y);
+
+    let run = script.run(&mut robot, &mut world, MAX_SCRIPT_TICKS).unwrap();
+
+    assert_eq!(run.ticks, 6);
+    assert_eq!(run.steps, vec!(Step::Moved, Step::Moved, Step::Turned, Step::Moved, Step::Moved, Step::Turned));
+    assert_eq!(robot.to_string(), "2 2 S");
+    assert!(!robot.is_lost);
+  }
+
+  #[test]
+  fn script_halts_after_a_collision() {
+    let mut world  = World::new(&3, &3);
+    let mut robot  = Robot::new(0, 0, 0, Heading::North);
+    let     script = RobotScript::compile("pusher", r#"fn next_command(robot) { if robot.tick < 4 { "F" } else { "" } }"#).unwrap();
+
+    world.rules.collisions = Collisions::Halt;
+    world.place_robot_at(&robot.id, &robot.x, &robot.y);
+    world.add_obstacle(&0, &2);
+
+    let run = script.run(&mut robot, &mut world, MAX_SCRIPT_TICKS).unwrap();
+
+    assert_eq!(run.steps, vec!(Step::Moved, Step::Blocked(0, 2)));
+    assert_eq!(robot.to_string(), "0 1 N");
+  }
+
+  #[test]
+  fn script_errors() {
+    let mut world = World::new(&3, &3);
+    let mut robot = Robot::new(0, 0, 0, Heading::North);
+
+    assert!(RobotScript::compile("empty", "let x = 1;").is_err());
+    assert!(RobotScript::compile("broken", "fn next_command(robot) {").is_err());
+
+    let script = RobotScript::compile("bad", r#"fn next_command(robot) { "X" }"#).unwrap();
+    assert_eq!(script.run(&mut robot, &mut world, 5), Err(String::from("bad: tick 0: invalid move/turn command 'X'")));
+
+    let script = RobotScript::compile("forever", "fn next_command(robot) { loop {} }").unwrap();
+    assert!(script.run(&mut robot, &mut world, 5).is_err());
+  }
+}
@@ -0,0 +1,180 @@
+use std::io::prelude::{BufRead, Write};
+use std::io::{self, BufReader};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+
+use crate::input::{classify, Line};
+use crate::rules::Rules;
+use crate::session::{Reply, Session, SessionError};
+use crate::validate::Validation;
+use crate::world::Limits;
+use crate::trace::Trace;
+
+const LIB_NAME     : &str  = module_path!();
+const TRACE_ACTIVE : &bool = &false;
+
+pub const DEFAULT_PORT : u16 = 7878;
+
+const RESPONSE_OK      : &str = "OK";
+const RESPONSE_ERROR   : &str = "ERR";
+const RESPONSE_WARNING : &str = "WARN";
+
+const ERROR_CODE_INCLUDE : &str = "INCLUDE_ERROR";
+const ERROR_NO_INCLUDES  : &str = "Files cannot be included over the network";
+
+// *********************************************************************************************************************
+// Listen on a local TCP port, giving each connection its own session
+// *********************************************************************************************************************
+pub fn serve(port : u16, validation : Validation, limits : Limits, rules : Rules) -> io::Result<()> {
+  const FN_NAME : &str = "serve";
+
+  let trace_boundary = Trace::make_boundary_trace_fn(TRACE_ACTIVE, LIB_NAME, FN_NAME);
+  let trace          = Trace::make_trace_fn(TRACE_ACTIVE, LIB_NAME, FN_NAME);
+
+  trace_boundary(&Some(true));
+
+  let listener = TcpListener::bind(("127.0.0.1", port))?;
+  eprintln!("Listening on {}", listener.local_addr()?);
+
+  for stream in listener.incoming() {
+    match stream {
+      Ok(stream) => {
+        trace(&format!("Connection from {:?}", stream.peer_addr()));
+        thread::spawn(move || {
+          if let Err(err) = handle_connection(stream, validation, limits, rules) {
+            eprintln!("Connection closed: {}", err);
+          }
+        });
+      }
+    , Err(err) => eprintln!("Failed to accept connection: {}", err)
+    }
+  }
+
+  trace_boundary(&Some(false));
+  Ok(())
+}
+
+fn handle_connection(stream : TcpStream, validation : Validation, limits : Limits, rules : Rules) -> io::Result<()> {
+  let reader = BufReader::new(stream.try_clone()?);
+  converse(reader, stream, validation, limits, rules)
+}
+
+// *********************************************************************************************************************
+// Speak the line protocol
+//
+// Each input line is answered by its output lines, then one "WARN <message>" line per warning, then either "OK" or
+// "ERR <code> <message>".  Blank lines and comments are not answered at all.  Includes are refused, since they would
+// let any client read any file the server can
+// *********************************************************************************************************************
+pub fn converse<R : BufRead, W : Write>( reader : R, mut writer : W, validation : Validation, limits : Limits, rules : Rules
+                                       ) -> io::Result<()> {
+  let mut session = Session::new(validation).with_limits(limits).with_rules(rules).without_files();
+
+  for line in reader.lines() {
+    let line = line?;
+
+    match classify(&line) {
+      Line::Blank | Line::Comment => continue
+    , Line::Include(_)          => writeln!(writer, "{} {} {}", RESPONSE_ERROR, ERROR_CODE_INCLUDE, ERROR_NO_INCLUDES)?
+    , Line::Command(cmd)        => answer(session.handle_line(cmd), &mut writer)?
+    }
+
+    writer.flush()?;
+  }
+
+  Ok(())
+}
+
+// *********************************************************************************************************************
+// Private API
+// *********************************************************************************************************************
+fn answer<W : Write>(result : Result<Reply, SessionError>, writer : &mut W) -> io::Result<()> {
+  match result {
+    Ok(reply) => {
+      for o in reply.output {
+        writeln!(writer, "{}", o)?;
+      }
+
+      for w in reply.warnings {
+        writeln!(writer, "{} {}", RESPONSE_WARNING, w)?;
+      }
+
+      writeln!(writer, "{}", RESPONSE_OK)
+    }
+  , Err(err) => writeln!(writer, "{} {} {}", RESPONSE_ERROR, err.code(), err.message())
+  }
+}
+
+// *********************************************************************************************************************
+// Suppose we'd better test it...
+// *********************************************************************************************************************
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::io::Cursor;
+
+  fn talk(input : &str) -> String {
+    let mut output : Vec<u8> = vec!();
+    converse(Cursor::new(input), &mut output, Validation::Strict, Limits::DEFAULT, Rules::default()).unwrap();
+    String::from_utf8(output).unwrap()
+  }
+
+  #[test]
+  fn robot_reports() {
+    assert_eq!( talk("5 3\n1 1 E\nRFRFRFRF\n3 2 N\nFRRFLLFFRRFLL\n")
+              , "OK\nOK\n1 1 E\nOK\nOK\n3 2 N LOST\nOK\n"
+              );
+  }
+
+  #[test]
+  fn structured_errors() {
+    assert_eq!( talk("F\n5 3\n1 1 E\nFxF R\n")
+              , "ERR INVALID_WORLD World width must be an integer\nOK\nOK\n\
+                 ERR INVALID_INSTRUCTIONS column 2: Invalid move/turn command 'x'; \
+                 column 5: Unexpected text after move/turn instructions 'R'\n"
+              );
+  }
+
+  #[test]
+  fn comments_and_includes() {
+    assert_eq!( talk("# The classic sample\n5 3\n\n1 1 E  # first\nRFRFRFRF\ninclude /etc/passwd\n")
+              , "OK\nOK\n1 1 E\nOK\nERR INCLUDE_ERROR Files cannot be included over the network\n"
+              );
+  }
+
+  #[test]
+  fn no_files_over_the_network() {
+    assert_eq!( talk("5 3\n1 1 E\n:script /etc/passwd\n")
+              , "OK\nOK\nERR SCRIPT_ERROR Scripts cannot be loaded from files in this session\n"
+              );
+  }
+
+  #[test]
+  fn sessions_are_independent() {
+    let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+    let addr     = listener.local_addr().unwrap();
+
+    thread::spawn(move || {
+      for stream in listener.incoming().take(2) {
+        let stream = stream.unwrap();
+        thread::spawn(move || handle_connection(stream, Validation::Strict, Limits::DEFAULT, Rules::default()));
+      }
+    });
+
+    let ask = |stream : &mut TcpStream, reader : &mut BufReader<TcpStream>, line : &str| {
+      writeln!(stream, "{}", line).unwrap();
+      let mut answer = String::new();
+      reader.read_line(&mut answer).unwrap();
+      answer
+    };
+
+    let mut first   = TcpStream::connect(addr).unwrap();
+    let mut second  = TcpStream::connect(addr).unwrap();
+    let mut reader1 = BufReader::new(first.try_clone().unwrap());
+    let mut reader2 = BufReader::new(second.try_clone().unwrap());
+
+    assert_eq!(ask(&mut first,  &mut reader1, "5 3"),   "OK\n");
+    assert_eq!(ask(&mut first,  &mut reader1, "1 1 E"), "OK\n");
+    assert_eq!(ask(&mut second, &mut reader2, ":show"), "ERR NO_WORLD There is no world yet - enter its width and height first\n");
+  }
+}
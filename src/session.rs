@@ -0,0 +1,1288 @@
+use std::fmt;
+use std::collections::BTreeMap;
+
+use crate::dry_run::dry_run;
+use crate::coords::{Coords, PARSE_ERROR_BAD_COORDS};
+use crate::mission::{score, Goal};
+use crate::program::{is_program, Program, MAX_PROGRAM_STEPS};
+use crate::scheduler::{run_lockstep, Scheduling, DIAG_PROGRAM_IN_LOCKSTEP};
+use crate::robot::{follow_pushes, place_declared_robot, Robot, Step, PROMPT_NEW_ROBOT, PROMPT_MOVE_TURN};
+use crate::scenario::{Block, Meta, Statement};
+use crate::script::{RobotScript, MAX_SCRIPT_TICKS};
+use crate::rules::Rules;
+use crate::validate::{check_instructions, mark_diagnostics, Diagnostic, Validation};
+use crate::world::{
+  Dialect
+, Dimensions
+, DimensionsError
+, EnergyCosts
+, Limits
+, World
+, DEFAULT_CHARGE_RATE
+, PARSE_ERROR_BAD_DIALECT
+, PROMPT_NEW_WORLD
+};
+use crate::trace::Trace;
+
+const LIB_NAME     : &str  = module_path!();
+const TRACE_ACTIVE : &bool = &false;
+
+pub const META_PREFIX        : char  = ':';
+pub const DEFAULT_WORLD_NAME : &str  = "main";
+const UNDO_LIMIT             : usize = 100;
+
+const ERROR_NO_WORLD          : &str = "There is no world yet - enter its width and height first";
+const ERROR_NO_ROBOT_SELECTED : &str = "No robot selected - create one or use :select <id>";
+const ERROR_NOTHING_TO_UNDO   : &str = "Nothing to undo";
+
+pub const ERROR_MISSING_WORLD_NAME : &str = "Please specify the new world's name, width and height";
+pub const ERROR_BAD_SCHEDULING     : &str = "Scheduling must be either sequential or lockstep";
+
+const ERROR_BAD_COSTS          : &str = "Energy costs must be given as two non-negative integers: forward turn";
+const ERROR_BAD_ITEM           : &str = "An item needs a location inside this world and a name";
+const ERROR_BAD_CHARGER        : &str = "A charging station needs a location inside this world and a charge rate of at least 1";
+const ERROR_BAD_OBSTACLE       : &str = "An obstacle needs an empty location inside this world";
+const ERROR_NO_FILE_ACCESS     : &str = "Scripts cannot be loaded from files in this session";
+
+pub const HELP_TEXT : &str = "\
+Robot definition      x y H [id=<id>] [name=<name>] [type=<type>] [energy=<energy>] [cargo=<limit>]
+                                        Create a robot at (x,y) heading N, E, S or W and select it
+Instructions          FLRWPD...         Move/turn the selected robot, wait (W) for a tick, pick up (P) or drop (D) an item
+                                        (in lockstep scheduling, the instructions are queued until :go)
+Program               REPEAT n { ... }  Loop and branch: REPEAT n, WHILE [NOT] test, IF [NOT] test ... ELSE
+                                        where test is FREE, EDGE, SCENTED or OCCUPIED (the location ahead)
+Dry run               ?FLR...           Show what the instructions would do without doing it
+:show                                   Print the world
+:robots                                 List all robots (* marks the selected robot)
+:select <id|name>                       Send subsequent instructions to robot <id> or <name>
+:scents                                 List the locations and headings where robots have been lost
+:script <file>|off                      Attach a Rhai script to (or detach it from) the selected robot
+:run [ticks]                            Let the selected robot's script drive it (default 100 ticks)
+:schedule [sequential|lockstep]         Show or change how instructions are carried out (default sequential)
+:validation [strict|lenient]            Show or change whether instruction lines with problems are rejected (default strict)
+:plans                                  List the instructions queued for each robot in the current world
+:go                                     Run all queued instructions in lockstep, one command per robot per tick
+:coords [<base> <origin>]               Show or change the coordinate convention: base 0 or 1, origin bottom-left or top-left
+:dialect [native|classic]               Show or change how new worlds are sized: width height, or upper-right x y
+:costs [<forward> <turn>]               Show or set the energy used by each forward step and turn
+:obstacle [<x> <y>]                     List the obstacles, or put one down where no robot can go
+:charger [<x> <y> [rate]]               List charging stations, or add one giving <rate> energy per tick (default 1)
+:item [<x> <y> <name>]                  List the items lying in the world, or put one down
+:goal [<goal>]                          List the current world's goals, or add one (see README)
+:score                                  Grade the robots in the current world against its goals
+:undo                                   Undo the last change to any world, or the last switch between worlds
+:reset [scents]                         Remove all robots (and optionally all scents) from the current world, keeping its goals
+:new <name> <width> <height>            Create a new world and switch to it
+:switch <name>                          Switch to another world
+:worlds                                 List all worlds (* marks the current world)
+:help                                   Show this help";
+
+// *********************************************************************************************************************
+// Errors reported back to whoever is driving the session
+// *********************************************************************************************************************
+#[derive(Debug, PartialEq)]
+pub enum SessionError {
+  NoWorld
+, InvalidWorld(&'static str)
+, InvalidDimensions(DimensionsError)
+, InvalidRobot(&'static str)
+, InvalidInstructions(String, Vec<Diagnostic>)
+, NoRobotSelected
+, UnknownRobot(String)
+, RobotLost(String)
+, NothingToUndo
+, UnknownCommand(String)
+, DuplicateWorld(String)
+, UnknownWorld(String)
+, NoScript(String)
+, Script(String)
+, InvalidGoal(&'static str)
+}
+
+impl fmt::Display for SessionError {
+  fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      SessionError::NoWorld                          => write!(fmt, "{}", ERROR_NO_WORLD)
+    , SessionError::InvalidWorld(msg)                => write!(fmt, "{}", msg)
+    , SessionError::InvalidDimensions(err)           => write!(fmt, "{}", err)
+    , SessionError::InvalidRobot(msg)                => write!(fmt, "{}", msg)
+    , SessionError::InvalidInstructions(line, diags) => {
+        for d in diags {
+          writeln!(fmt, "{}", d)?;
+        }
+        write!(fmt, "{}", mark_diagnostics(line, diags))
+      }
+    , SessionError::NoRobotSelected                  => write!(fmt, "{}", ERROR_NO_ROBOT_SELECTED)
+    , SessionError::UnknownRobot(id)                 => write!(fmt, "No robot with id or name {}", id)
+    , SessionError::RobotLost(label)                 => write!(fmt, "Robot {} is lost and cannot obey instructions", label)
+    , SessionError::NothingToUndo                    => write!(fmt, "{}", ERROR_NOTHING_TO_UNDO)
+    , SessionError::UnknownCommand(cmd)              => write!(fmt, "Unknown command '{}' - try :help", cmd)
+    , SessionError::DuplicateWorld(name)             => write!(fmt, "A world called {} already exists", name)
+    , SessionError::UnknownWorld(name)               => write!(fmt, "No world called {}", name)
+    , SessionError::NoScript(label)                  => write!(fmt, "Robot {} has no script - use :script <file>", label)
+    , SessionError::Script(msg)                      => write!(fmt, "{}", msg)
+    , SessionError::InvalidGoal(msg)                 => write!(fmt, "{}", msg)
+    }
+  }
+}
+
+impl SessionError {
+  // Machine readable error code
+  pub fn code(&self) -> &'static str {
+    match self {
+      SessionError::NoWorld                   => "NO_WORLD"
+    , SessionError::InvalidWorld(_)           => "INVALID_WORLD"
+    , SessionError::InvalidDimensions(_)      => "INVALID_WORLD"
+    , SessionError::InvalidRobot(_)           => "INVALID_ROBOT"
+    , SessionError::InvalidInstructions(..)   => "INVALID_INSTRUCTIONS"
+    , SessionError::NoRobotSelected           => "NO_ROBOT_SELECTED"
+    , SessionError::UnknownRobot(_)           => "UNKNOWN_ROBOT"
+    , SessionError::RobotLost(_)              => "ROBOT_LOST"
+    , SessionError::NothingToUndo             => "NOTHING_TO_UNDO"
+    , SessionError::UnknownCommand(_)         => "UNKNOWN_COMMAND"
+    , SessionError::DuplicateWorld(_)         => "DUPLICATE_WORLD"
+    , SessionError::UnknownWorld(_)           => "UNKNOWN_WORLD"
+    , SessionError::NoScript(_)               => "NO_SCRIPT"
+    , SessionError::Script(_)                 => "SCRIPT_ERROR"
+    , SessionError::InvalidGoal(_)            => "INVALID_GOAL"
+    }
+  }
+
+  // The error message on a single line
+  pub fn message(&self) -> String {
+    match self {
+      SessionError::InvalidInstructions(_, diags) => diags.iter().map(|d| d.to_string()).collect::<Vec<String>>().join("; ")
+    , _                                           => self.to_string()
+    }
+  }
+}
+
+// *********************************************************************************************************************
+// The result of successfully handling one line of input
+// *********************************************************************************************************************
+#[derive(Debug, Default, PartialEq)]
+pub struct Reply {
+  pub output   : Vec<String>    // Robot reports, world renderings etc.
+, pub warnings : Vec<String>    // Things that went wrong but didn't stop the line being obeyed
+}
+
+impl Reply {
+  fn say(text : String) -> Reply {
+    Reply { output : vec!(text), warnings : vec!() }
+  }
+}
+
+// *********************************************************************************************************************
+// A world, the robots in it and which of them is receiving instructions
+// *********************************************************************************************************************
+#[derive(Debug, Clone)]
+struct NamedWorld {
+  name                  : String
+, world                 : World
+, robots                : Vec<Robot>
+, selected              : Option<i32>
+, awaiting_instructions : bool
+, scripts               : BTreeMap<i32, RobotScript>
+, goals                 : Vec<Goal>
+, plans                 : BTreeMap<i32, String>   // Instructions queued for lockstep scheduling
+}
+
+impl NamedWorld {
+  fn new(name : &str, world : World) -> NamedWorld {
+    NamedWorld {
+      name                  : name.to_string()
+    , world
+    , robots                : vec!()
+    , selected              : None
+    , awaiting_instructions : false
+    , scripts               : BTreeMap::new()
+    , goals                 : vec!()
+    , plans                 : BTreeMap::new()
+    }
+  }
+
+  fn selected_index(&self) -> Result<usize, SessionError> {
+    let id  = self.selected.ok_or(SessionError::NoRobotSelected)?;
+    let idx = self.robots.iter().position(|r| r.id == id).ok_or(SessionError::NoRobotSelected)?;
+
+    if self.robots[idx].is_lost {
+      Err(SessionError::RobotLost(self.robots[idx].label()))
+    }
+    else {
+      Ok(idx)
+    }
+  }
+
+  fn describe(&self, robot : &Robot) -> String {
+    let marker = if self.selected == Some(robot.id) { "*" } else { " " };
+
+    match &robot.name {
+      Some(name) => format!("{}{} {}: {}", marker, robot.id, name, robot.status_in(&self.world))
+    , None       => format!("{}{}: {}", marker, robot.id, robot.status_in(&self.world))
+    }
+  }
+}
+
+// *********************************************************************************************************************
+// Everything that :undo can roll back
+// *********************************************************************************************************************
+#[derive(Debug, Clone, Default)]
+struct State {
+  worlds  : Vec<NamedWorld>
+, current : Option<usize>
+}
+
+// *********************************************************************************************************************
+// Session definition
+// *********************************************************************************************************************
+#[derive(Debug)]
+pub struct Session {
+  state      : State
+, undo       : Vec<State>
+, validation : Validation
+, scheduling : Scheduling
+, coords     : Coords         // The coordinate convention for new worlds
+, dialect    : Dialect        // How the size of new worlds is given
+, limits     : Limits         // The largest world that may be created
+, rules      : Rules          // The rules new worlds are played by
+, files      : bool           // Whether commands may read files, which network clients must not
+}
+
+// *********************************************************************************************************************
+// Session implementation
+// *********************************************************************************************************************
+impl Session {
+  pub fn world_name(&self) -> Option<&str> {
+    self.current().ok().map(|nw| nw.name.as_str())
+  }
+
+  pub fn world(&self) -> Option<&World> {
+    self.current().ok().map(|nw| &nw.world)
+  }
+
+  pub fn robots(&self) -> &[Robot] {
+    self.current().map_or(&[], |nw| &nw.robots)
+  }
+
+  pub fn selected(&self) -> Option<&Robot> {
+    let nw = self.current().ok()?;
+    nw.selected.and_then(|id| nw.robots.iter().find(|r| r.id == id))
+  }
+
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  // What are we waiting for?
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  pub fn prompt(&self) -> &'static str {
+    match self.current() {
+      Err(_)                               => PROMPT_NEW_WORLD
+    , Ok(nw) if nw.awaiting_instructions   => PROMPT_MOVE_TURN
+    , Ok(_)                                => PROMPT_NEW_ROBOT
+    }
+  }
+
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  // The grades for every world that has goals, to be printed once the input has run out
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  pub fn final_report(&self) -> Vec<String> {
+    self.state.worlds.iter()
+      .filter(|nw| !nw.goals.is_empty())
+      .flat_map(|nw| {
+        let mut report = vec!(format!("World {}:", nw.name));
+        report.extend(score(&nw.goals, &nw.robots, &nw.world));
+        report
+      })
+      .collect()
+  }
+
+  // Has every goal in every world been met?  (Trivially true when there are no goals)
+  pub fn goals_met(&self) -> bool {
+    self.state.worlds.iter().all(|nw| nw.goals.iter().all(|goal| goal.is_met(&nw.robots, &nw.world)))
+  }
+
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  // Handle one line of input
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  pub fn handle_line(&mut self, line : &str) -> Result<Reply, SessionError> {
+    let statement = Statement::parse(line.trim(), self.state.current.is_some(), self.dialect, &self.limits)?;
+    self.handle_statement(&statement)
+  }
+
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  // Handle one statement, whether parsed from a line just now or from a whole scenario beforehand
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  pub fn handle_statement(&mut self, statement : &Statement) -> Result<Reply, SessionError> {
+    const FN_NAME : &str = "handle_statement";
+
+    let trace_boundary = Trace::make_boundary_trace_fn(TRACE_ACTIVE, LIB_NAME, FN_NAME);
+    let trace          = Trace::make_trace_fn(TRACE_ACTIVE, LIB_NAME, FN_NAME);
+
+    trace_boundary(&Some(true));
+    trace(&format!("{:?}", statement));
+
+    let result = match statement {
+      Statement::World(decl)         => self.new_world(decl.name.as_deref().unwrap_or(DEFAULT_WORLD_NAME), decl.dims)
+    , Statement::Robot(decl)         => self.new_robot(&decl.robot)
+    , Statement::Instructions(block) => self.instructions(block)
+    , Statement::DryRun(block)       => self.dry_run(&block.text)
+    , Statement::Meta(cmd)           => self.meta_command(cmd.name, &cmd.args)
+    };
+
+    trace_boundary(&Some(false));
+    result
+  }
+
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  // Input line handlers
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  fn new_world(&mut self, name : &str, dims : Dimensions) -> Result<Reply, SessionError> {
+    if self.state.worlds.iter().any(|nw| nw.name == name) {
+      return Err(SessionError::DuplicateWorld(name.to_string()))
+    }
+
+    let mut world = World::new(&dims.width, &dims.height);
+    world.coords = self.coords;
+    world.rules  = self.rules;
+
+    self.save_state();
+    self.state.worlds.push(NamedWorld::new(name, world));
+    self.state.current = Some(self.state.worlds.len() - 1);
+    Ok(Reply::default())
+  }
+
+  fn new_robot(&mut self, robot : &Robot) -> Result<Reply, SessionError> {
+    let mut world = self.current()?.world.clone();
+    let     robot = place_declared_robot(robot.clone(), &mut world).map_err(SessionError::InvalidRobot)?;
+
+    self.save_state();
+
+    let nw = self.current_mut()?;
+    nw.world                 = world;
+    nw.selected              = Some(robot.id);
+    nw.awaiting_instructions = true;
+    nw.robots.push(robot);
+    Ok(Reply::default())
+  }
+
+  fn instructions(&mut self, block : &Block) -> Result<Reply, SessionError> {
+    if self.scheduling == Scheduling::Lockstep {
+      return self.queue(block)
+    }
+
+    let orders = Orders::check(&block.text, self.validation).map_err(|diags| SessionError::InvalidInstructions(block.text.clone(), diags))?;
+    let idx    = self.current()?.selected_index()?;
+
+    self.save_state();
+
+    let nw                = self.current_mut()?;
+    let robot             = &mut nw.robots[idx];
+    let (steps, warnings) = orders.obey(robot, &mut nw.world);
+
+    let mut reply = Reply::say(robot.report(&nw.world));
+    reply.warnings = warnings;
+
+    follow_pushes(&mut nw.robots, &steps, &nw.world);
+    nw.awaiting_instructions = false;
+    Ok(reply)
+  }
+
+  // In lockstep scheduling, instructions are saved up until all robots are ready to go
+  fn queue(&mut self, block : &Block) -> Result<Reply, SessionError> {
+    if block.is_program {
+      let diag = Diagnostic { column : 1, found : None, message : DIAG_PROGRAM_IN_LOCKSTEP };
+      return Err(SessionError::InvalidInstructions(block.text.clone(), vec!(diag)))
+    }
+
+    let (cmds, warnings) = self.validate(&block.text)?;
+    let idx = self.current()?.selected_index()?;
+
+    self.save_state();
+
+    let nw = self.current_mut()?;
+    nw.plans.entry(nw.robots[idx].id).or_default().push_str(&cmds);
+    nw.awaiting_instructions = false;
+    Ok(Reply { output : vec!(), warnings })
+  }
+
+  fn dry_run(&self, line : &str) -> Result<Reply, SessionError> {
+    let (cmds, warnings) = self.validate(line)?;
+    let nw  = self.current()?;
+    let idx = nw.selected_index()?;
+
+    let mut reply = Reply::say(dry_run(&nw.robots[idx], &cmds, &nw.world).describe(&nw.world));
+    reply.warnings = warnings;
+    Ok(reply)
+  }
+
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  // Meta commands
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  fn meta_command(&mut self, name : Meta, args : &[String]) -> Result<Reply, SessionError> {
+    let mut args = args.iter().map(String::as_str);
+
+    match name {
+      Meta::Help   => Ok(Reply::say(HELP_TEXT.to_string()))
+    , Meta::Show   => Ok(Reply::say(self.current()?.world.to_string()))
+    , Meta::Robots => {
+        let nw = self.current()?;
+        Ok(Reply { output : nw.robots.iter().map(|r| nw.describe(r)).collect(), warnings : vec!() })
+      }
+    , Meta::Scents => {
+        let world = &self.current()?.world;
+
+        Ok(Reply {
+          output   : world.scents().iter()
+                       .map(|(x, y, h)| {
+                         let (x, y) = world.to_external(x, y);
+                         format!("{} {} {}", x, y, h)
+                       })
+                       .collect()
+        , warnings : vec!()
+        })
+      }
+    , Meta::Select => {
+        let id = args.next().unwrap_or("");
+        let nw = self.current_mut()?;
+
+        match nw.robots.iter().position(|r| r.is_called(id)) {
+          Some(idx) => {
+            nw.selected              = Some(nw.robots[idx].id);
+            nw.awaiting_instructions = !nw.robots[idx].is_lost;
+            Ok(Reply::say(nw.describe(&nw.robots[idx])))
+          }
+        , None => Err(SessionError::UnknownRobot(id.to_string()))
+        }
+      }
+    , Meta::Undo =>
+        match self.undo.pop() {
+          Some(state) => { self.state = state; Ok(Reply::default()) }
+        , None        => Err(SessionError::NothingToUndo)
+        }
+    , Meta::Reset => {
+        let keep_scents = match args.next() {
+          None           => true
+        , Some("scents") => false
+        , Some(other)    => return Err(SessionError::UnknownCommand(format!("{}reset {}", META_PREFIX, other)))
+        };
+
+        let     nw    = self.current()?;
+        let mut world = nw.world.without_robots();
+
+        if !keep_scents {
+          world.clear_scents();
+        }
+
+        // The robots go, but the mission stays
+        let mut fresh = NamedWorld::new(&nw.name, world);
+        fresh.goals   = nw.goals.clone();
+
+        self.save_state();
+        *self.current_mut()? = fresh;
+        Ok(Reply::default())
+      }
+    , Meta::Obstacle => {
+        let args : Vec<&str> = args.collect();
+
+        if args.is_empty() {
+          let world = &self.current()?.world;
+
+          return Ok(Reply {
+            output   : world.obstacles().iter()
+                         .map(|(x, y)| {
+                           let (x, y) = world.to_external(x, y);
+                           format!("{} {}", x, y)
+                         })
+                         .collect()
+          , warnings : vec!()
+          })
+        }
+
+        let world = &self.current()?.world;
+
+        match args[..] {
+          [x, y] => match (x.parse::<i32>(), y.parse::<i32>()) {
+            (Ok(x), Ok(y)) if is_inside(world, x, y) => {
+              let (x, y) = world.to_internal(&x, &y);
+
+              if world.is_location_occupied(&x, &y) {
+                return Err(SessionError::InvalidWorld(ERROR_BAD_OBSTACLE))
+              }
+
+              self.save_state();
+              self.current_mut()?.world.add_obstacle(&x, &y);
+              Ok(Reply::default())
+            }
+          , _ => Err(SessionError::InvalidWorld(ERROR_BAD_OBSTACLE))
+          }
+        , _ => Err(SessionError::InvalidWorld(ERROR_BAD_OBSTACLE))
+        }
+      }
+    , Meta::Charger => {
+        let args : Vec<&str> = args.collect();
+
+        if args.is_empty() {
+          let world = &self.current()?.world;
+
+          return Ok(Reply {
+            output   : world.chargers().iter()
+                         .map(|(x, y, rate)| {
+                           let (x, y) = world.to_external(x, y);
+                           format!("{} {} +{}", x, y, rate)
+                         })
+                         .collect()
+          , warnings : vec!()
+          })
+        }
+
+        let (x, y, rate) = match args[..] {
+          [x, y]       => (x.parse::<i32>(), y.parse::<i32>(), Ok(DEFAULT_CHARGE_RATE))
+        , [x, y, rate] => (x.parse::<i32>(), y.parse::<i32>(), rate.parse::<u32>())
+        , _            => return Err(SessionError::InvalidWorld(ERROR_BAD_CHARGER))
+        };
+
+        let world = &self.current()?.world;
+
+        match (x, y, rate) {
+          (Ok(x), Ok(y), Ok(rate)) if rate > 0 && is_inside(world, x, y) => {
+            let (x, y) = world.to_internal(&x, &y);
+            self.save_state();
+            self.current_mut()?.world.add_charger(&x, &y, rate);
+            Ok(Reply::default())
+          }
+        , _ => Err(SessionError::InvalidWorld(ERROR_BAD_CHARGER))
+        }
+      }
+    , Meta::Item => {
+        let args : Vec<&str> = args.collect();
+
+        if args.is_empty() {
+          let world = &self.current()?.world;
+
+          return Ok(Reply {
+            output   : world.items().iter()
+                         .map(|(x, y, item)| {
+                           let (x, y) = world.to_external(x, y);
+                           format!("{} {} {}", x, y, item)
+                         })
+                         .collect()
+          , warnings : vec!()
+          })
+        }
+
+        let world = &self.current()?.world;
+
+        match args[..] {
+          [x, y, item] => match (x.parse::<i32>(), y.parse::<i32>()) {
+            (Ok(x), Ok(y)) if is_inside(world, x, y) => {
+              let (x, y) = world.to_internal(&x, &y);
+              self.save_state();
+              self.current_mut()?.world.put_item(&x, &y, item);
+              Ok(Reply::default())
+            }
+          , _ => Err(SessionError::InvalidWorld(ERROR_BAD_ITEM))
+          }
+        , _ => Err(SessionError::InvalidWorld(ERROR_BAD_ITEM))
+        }
+      }
+    , Meta::Goal => {
+        let spec = args.collect::<Vec<&str>>().join(" ");
+
+        if spec.is_empty() {
+          let nw = self.current()?;
+          return Ok(Reply {
+            output   : nw.goals.iter()
+                         .enumerate()
+                         .map(|(idx, goal)| format!("Goal {}: {}", idx + 1, goal.map_cells(|x, y| nw.world.to_external(&x, &y))))
+                         .collect()
+          , warnings : vec!()
+          })
+        }
+
+        let goal  = spec.parse::<Goal>().map_err(SessionError::InvalidGoal)?;
+        let world = &self.current()?.world;
+        let goal  = goal.map_cells(|x, y| world.to_internal(&x, &y));
+
+        self.save_state();
+        self.current_mut()?.goals.push(goal);
+        Ok(Reply::default())
+      }
+    , Meta::Score => {
+        let nw = self.current()?;
+        Ok(Reply { output : score(&nw.goals, &nw.robots, &nw.world), warnings : vec!() })
+      }
+    , Meta::Schedule => {
+        self.scheduling = match args.next() {
+          None               => return Ok(Reply::say(self.scheduling.to_string()))
+        , Some("sequential") => Scheduling::Sequential
+        , Some("lockstep")   => Scheduling::Lockstep
+        , Some(_)            => return Err(SessionError::InvalidWorld(ERROR_BAD_SCHEDULING))
+        };
+        Ok(Reply::default())
+      }
+    , Meta::Validation => {
+        self.validation = match args.next() {
+          None       => return Ok(Reply::say(self.validation.to_string()))
+        , Some(mode) => mode.parse::<Validation>().map_err(SessionError::InvalidWorld)?
+        };
+        Ok(Reply::default())
+      }
+    , Meta::Plans => {
+        let nw = self.current()?;
+        Ok(Reply {
+          output   : nw.robots.iter()
+                       .filter_map(|r| nw.plans.get(&r.id).map(|cmds| format!("{}: {}", r.label(), cmds)))
+                       .collect()
+        , warnings : vec!()
+        })
+      }
+    , Meta::Go => self.run_plans()
+    , Meta::Coords => {
+        let spec = args.collect::<Vec<&str>>().join(" ");
+
+        if spec.is_empty() {
+          let coords = self.current().map_or(self.coords, |nw| nw.world.coords);
+          return Ok(Reply::say(coords.to_string()))
+        }
+
+        self.coords = spec.parse::<Coords>().map_err(|_| SessionError::InvalidWorld(PARSE_ERROR_BAD_COORDS))?;
+
+        if self.current().is_ok() {
+          let coords = self.coords;
+          self.save_state();
+          self.current_mut()?.world.coords = coords;
+        }
+
+        Ok(Reply::default())
+      }
+    , Meta::Dialect => {
+        self.dialect = match args.next() {
+          None          => return Ok(Reply::say(self.dialect.to_string()))
+        , Some(dialect) => dialect.parse::<Dialect>().map_err(|_| SessionError::InvalidWorld(PARSE_ERROR_BAD_DIALECT))?
+        };
+        Ok(Reply::default())
+      }
+    , Meta::Costs => {
+        let costs : Vec<&str> = args.collect();
+
+        if costs.is_empty() {
+          let costs = self.current()?.world.costs;
+          return Ok(Reply::say(format!("forward {} turn {}", costs.forward, costs.turn)))
+        }
+
+        let (forward, turn) = match costs[..] {
+          [forward, turn] => match (forward.parse::<u32>(), turn.parse::<u32>()) {
+            (Ok(forward), Ok(turn)) => (forward, turn)
+          , _                       => return Err(SessionError::InvalidWorld(ERROR_BAD_COSTS))
+          }
+        , _ => return Err(SessionError::InvalidWorld(ERROR_BAD_COSTS))
+        };
+
+        self.current()?;
+        self.save_state();
+        self.current_mut()?.world.costs = EnergyCosts { forward, turn };
+        Ok(Reply::default())
+      }
+    , Meta::Switch => {
+        let name = args.next().unwrap_or("");
+
+        match self.state.worlds.iter().position(|nw| nw.name == name) {
+          Some(idx) => {
+            self.save_state();
+            self.state.current = Some(idx);
+            Ok(Reply::default())
+          }
+        , None => Err(SessionError::UnknownWorld(name.to_string()))
+        }
+      }
+    , Meta::Worlds => Ok(Reply {
+        output   : self.state.worlds.iter().enumerate().map(|(idx, nw)|
+                     format!( "{}{} {}x{} ({} robots)"
+                            , if self.state.current == Some(idx) { "*" } else { " " }
+                            , nw.name, nw.world.width, nw.world.height, nw.robots.len()
+                            )
+                   ).collect()
+      , warnings : vec!()
+      })
+    , Meta::Script => {
+        let arg = args.next().unwrap_or("");
+        let idx = self.current()?.selected_index()?;
+
+        let script = match arg {
+          "off"            => None
+        , _ if !self.files => return Err(SessionError::Script(String::from(ERROR_NO_FILE_ACCESS)))
+        , _                => Some(RobotScript::load(arg).map_err(SessionError::Script)?)
+        };
+
+        self.save_state();
+
+        let nw = self.current_mut()?;
+        let id = nw.robots[idx].id;
+
+        match script {
+          Some(script) => { nw.scripts.insert(id, script); }
+        , None         => { nw.scripts.remove(&id); }
+        }
+
+        Ok(Reply::default())
+      }
+    , Meta::Run => {
+        let ticks = match args.next() {
+          None    => MAX_SCRIPT_TICKS
+        , Some(n) => n.parse::<u32>().map_err(|_| SessionError::UnknownCommand(format!("{}run {}", META_PREFIX, n)))?
+        };
+        self.run_script(ticks)
+      }
+    }
+  }
+
+  fn run_script(&mut self, ticks : u32) -> Result<Reply, SessionError> {
+    let idx = self.current()?.selected_index()?;
+    let nw  = self.current()?;
+
+    let script = nw.scripts.get(&nw.robots[idx].id).cloned().ok_or_else(|| SessionError::NoScript(nw.robots[idx].label()))?;
+
+    // Run against copies so that a failing script leaves everything as it was
+    let mut world = nw.world.clone();
+    let mut robot = nw.robots[idx].clone();
+    let     run   = script.run(&mut robot, &mut world, ticks).map_err(SessionError::Script)?;
+
+    let mut reply = Reply::say(robot.report(&world));
+    reply.warnings = step_warnings(&robot, &run.steps, &world);
+
+    self.save_state();
+
+    let nw = self.current_mut()?;
+    nw.world       = world;
+    nw.robots[idx] = robot;
+    follow_pushes(&mut nw.robots, &run.steps, &nw.world);
+    Ok(reply)
+  }
+
+  // Report every robot that had something to do, in the order in which they took their turns
+  fn run_plans(&mut self) -> Result<Reply, SessionError> {
+    if self.current()?.plans.is_empty() {
+      return Ok(Reply::default())
+    }
+
+    self.save_state();
+
+    let nw       = self.current_mut()?;
+    let plans    = std::mem::take(&mut nw.plans);
+    let schedule = run_lockstep(&mut nw.robots, &plans, &mut nw.world);
+
+    let mut robots : Vec<&Robot> = nw.robots.iter().filter(|r| schedule.steps.contains_key(&r.id)).collect();
+    robots.sort_by_key(|r| r.id);
+
+    let mut reply = Reply::default();
+
+    for robot in robots {
+      reply.output.push(robot.report(&nw.world));
+      reply.warnings.extend(step_warnings(robot, &schedule.steps[&robot.id], &nw.world));
+    }
+
+    let label  = |id : i32| nw.robots.iter().find(|r| r.id == id).map_or(id.to_string(), |r| r.label());
+    let labels = |ids : &[i32]| ids.iter().map(|id| label(*id)).collect::<Vec<String>>().join(", ");
+
+    for c in &schedule.conflicts {
+      let (x, y) = nw.world.to_external(&c.x, &c.y);
+      reply.warnings.push(format!("Tick {}: robot {} gave way to robot {} at ({},{})", c.tick, label(c.robot), label(c.winner), x, y));
+    }
+
+    for d in &schedule.deadlocks {
+      reply.warnings.push(format!("Tick {}: deadlock - robots {} are blocking each other", d.tick, labels(&d.robots)));
+    }
+
+    if let Some(l) = &schedule.livelock {
+      reply.warnings.push(format!( "Tick {}: livelock - back where they were at tick {}, robots {} keep getting in each other's way"
+                                 , l.tick, l.since, labels(&l.robots)
+                                 ));
+    }
+
+    Ok(reply)
+  }
+
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  // Helpers
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  fn current(&self) -> Result<&NamedWorld, SessionError> {
+    self.state.current.map(|idx| &self.state.worlds[idx]).ok_or(SessionError::NoWorld)
+  }
+
+  fn current_mut(&mut self) -> Result<&mut NamedWorld, SessionError> {
+    match self.state.current {
+      Some(idx) => Ok(&mut self.state.worlds[idx])
+    , None      => Err(SessionError::NoWorld)
+    }
+  }
+
+  fn validate(&self, line : &str) -> Result<(String, Vec<String>), SessionError> {
+    let (cmds, diags) = check_instructions(line);
+
+    if diags.is_empty() {
+      Ok((cmds, vec!()))
+    }
+    else {
+      match self.validation {
+        Validation::Strict  => Err(SessionError::InvalidInstructions(line.to_string(), diags))
+      , Validation::Lenient => Ok((cmds, diags.iter().map(|d| d.to_string()).collect()))
+      }
+    }
+  }
+
+  fn save_state(&mut self) {
+    if self.undo.len() == UNDO_LIMIT {
+      self.undo.remove(0);
+    }
+
+    self.undo.push(self.state.clone());
+  }
+
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  // Constructor
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  pub fn new(validation : Validation) -> Session {
+    Session {
+      state      : State::default()
+    , undo       : vec!()
+    , validation
+    , scheduling : Scheduling::Sequential
+    , coords     : Coords::default()
+    , dialect    : Dialect::default()
+    , limits     : Limits::DEFAULT
+    , rules      : Rules::default()
+    , files      : true
+    }
+  }
+
+  // Start in a different input dialect, so that files written for it run unchanged
+  pub fn with_dialect(mut self, dialect : Dialect) -> Session {
+    self.dialect = dialect;
+    self
+  }
+
+  // Allow larger (or only smaller) worlds than usual
+  pub fn with_limits(mut self, limits : Limits) -> Session {
+    self.limits = limits;
+    self
+  }
+
+  // Play every new world by other than the default rules
+  pub fn with_rules(mut self, rules : Rules) -> Session {
+    self.rules = rules;
+    self
+  }
+
+  // Refuse any command that would read a file, for sessions driven by network clients
+  pub fn without_files(mut self) -> Session {
+    self.files = false;
+    self
+  }
+}
+
+// *********************************************************************************************************************
+// A line of instructions, checked and ready for a robot to obey
+// *********************************************************************************************************************
+pub enum Orders {
+  Commands(String, Vec<String>)   // The valid move/turn commands, and a warning about each problem skipped over
+, Program(Program)
+}
+
+impl Orders {
+  // Structural errors can't be skipped over, so programs are rejected outright even in lenient mode
+  pub fn check(line : &str, validation : Validation) -> Result<Orders, Vec<Diagnostic>> {
+    if is_program(line) {
+      return Program::compile(line).map(Orders::Program).map_err(|diag| vec!(diag))
+    }
+
+    let (cmds, diags) = check_instructions(line);
+
+    match validation {
+      Validation::Strict if !diags.is_empty() => Err(diags)
+    , _                                       => Ok(Orders::Commands(cmds, diags.iter().map(|d| d.to_string()).collect()))
+    }
+  }
+
+  // Have the robot obey the orders, returning the steps it took and the warnings to pass on
+  pub fn obey(self, robot : &mut Robot, world : &mut World) -> (Vec<Step>, Vec<String>) {
+    match self {
+      Orders::Commands(cmds, mut warnings) => {
+        let steps = robot.turn_and_move(&cmds, world);
+        warnings.extend(step_warnings(robot, &steps, world));
+        (steps, warnings)
+      }
+    , Orders::Program(program) => {
+        let     result   = program.run(robot, world, MAX_PROGRAM_STEPS);
+        let mut warnings = step_warnings(robot, &result.steps, world);
+
+        if result.exhausted {
+          warnings.push(format!("Robot {} stopped after {} program steps", robot.label(), MAX_PROGRAM_STEPS));
+        }
+
+        (result.steps, warnings)
+      }
+    }
+  }
+}
+
+// *********************************************************************************************************************
+// Warnings about the commands that a robot could not obey
+// *********************************************************************************************************************
+pub fn step_warnings(robot : &Robot, steps : &[Step], world : &World) -> Vec<String> {
+  let mut warnings : Vec<String> = steps.iter()
+    .filter_map(|step| match step {
+       Step::Blocked(x, y) => {
+         let what           = if world.is_obstacle(x, y) { "blocked by an obstacle" } else { "already occupied" };
+         let (ext_x, ext_y) = world.to_external(x, y);
+         Some(format!("Robot {} can't move to ({},{}) - location {}", robot.label(), ext_x, ext_y, what))
+       }
+     , Step::Stopped(x, y) => {
+         let what           = if world.is_obstacle(x, y) { "blocked by an obstacle" } else { "already occupied" };
+         let (ext_x, ext_y) = world.to_external(x, y);
+         Some(format!("Robot {} stopped short of ({},{}) - location {}", robot.label(), ext_x, ext_y, what))
+       }
+     , _ => None
+     })
+    .collect();
+
+  for step in steps {
+    match step {
+      Step::Overloaded => warnings.push(format!("Robot {} can't carry any more items", robot.label()))
+    , Step::NoItem     => warnings.push(format!("Robot {} has nothing to pick up or drop", robot.label()))
+    , _                => ()
+    }
+  }
+
+  let ignored = steps.iter().filter(|step| **step == Step::Depleted).count();
+
+  if ignored > 0 {
+    warnings.push(format!("Robot {} is out of energy - ignored {} command(s)", robot.label(), ignored));
+  }
+
+  warnings
+}
+
+// *********************************************************************************************************************
+// Private API
+// *********************************************************************************************************************
+
+// Does a location given in the world's coordinate convention lie inside it?
+fn is_inside(world : &World, x : i32, y : i32) -> bool {
+  let (x, y) = world.to_internal(&x, &y);
+  world.contains(&x, &y)
+}
+
+// *********************************************************************************************************************
+// Suppose we'd better test it...
+// *********************************************************************************************************************
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::mission::PARSE_ERROR_BAD_GOAL;
+  use crate::test_dir::TestDir;
+
+  fn run(session : &mut Session, lines : &[&str]) -> Vec<String> {
+    lines.iter().flat_map(|l| session.handle_line(l).unwrap().output).collect()
+  }
+
+  #[test]
+  fn classic_input() {
+    let mut session = Session::new(Validation::Strict);
+    let     output  = run(&mut session, &["6 4", "1 1 E", "RFRFRFRF", "3 2 N", "FRRFLLFFRRFLL", "0 3 W", "LLFFFLFLFL"]);
+
+    assert_eq!(output, vec!("1 1 E", "3 3 N LOST", "2 3 S"));
+  }
+
+  #[test]
+  fn meta_commands() {
+    let mut session = Session::new(Validation::Strict);
+    run(&mut session, &["6 4", "1 1 E", "3 2 N", "FRRFLLFFRRFLL"]);
+
+    assert_eq!(run(&mut session, &[":robots"]), vec!(" 0: 1 1 E", "*1: 3 3 N LOST"));
+    assert_eq!(run(&mut session, &[":scents"]), vec!("3 3 N"));
+    assert_eq!(session.handle_line("F"), Err(SessionError::RobotLost(String::from("1"))));
+    assert_eq!(session.handle_line(":select 7"), Err(SessionError::UnknownRobot(String::from("7"))));
+
+    run(&mut session, &[":select 0", "FF"]);
+    assert_eq!(session.selected().unwrap().to_string(), "3 1 E");
+
+    run(&mut session, &[":undo"]);
+    assert_eq!(session.selected().unwrap().to_string(), "1 1 E");
+
+    run(&mut session, &[":reset"]);
+    assert!(session.robots().is_empty());
+    assert_eq!(session.world().unwrap().scents().len(), 1);
+    assert!(!session.world().unwrap().is_location_occupied(&1, &1));
+
+    run(&mut session, &["1 1 N", ":reset scents"]);
+    assert!(session.robots().is_empty());
+    assert!(session.world().unwrap().scents().is_empty());
+    assert_eq!(session.handle_line(":bogus"), Err(SessionError::UnknownCommand(String::from(":bogus"))));
+  }
+
+  #[test]
+  fn named_robots() {
+    let mut session = Session::new(Validation::Strict);
+    run(&mut session, &["5 3", "1 1 E name=scout", "0 0 N id=5"]);
+
+    assert_eq!(run(&mut session, &[":select scout", "F"]), vec!("*0 scout: 1 1 E", "scout: 2 1 E"));
+    assert_eq!(run(&mut session, &[":robots"]), vec!("*0 scout: 2 1 E", " 5: 0 0 N"));
+    assert!(session.handle_line("2 2 N name=scout").is_err());
+  }
+
+  #[test]
+  fn multiple_worlds() {
+    let mut session = Session::new(Validation::Strict);
+    run(&mut session, &["5 3", "1 1 E", ":new second 2 2", "0 0 N", "F"]);
+
+    assert_eq!(session.world_name(), Some("second"));
+    assert_eq!(run(&mut session, &[":worlds"]), vec!(" main 5x3 (1 robots)", "*second 2x2 (1 robots)"));
+    assert_eq!(run(&mut session, &[":switch main", "F"]), vec!("2 1 E"));
+    assert_eq!(session.handle_line(":new main 3 3"), Err(SessionError::DuplicateWorld(String::from("main"))));
+    assert_eq!(session.handle_line(":switch third"), Err(SessionError::UnknownWorld(String::from("third"))));
+
+    // Undoing the switch goes back to the other world, leaving what was done there alone
+    run(&mut session, &[":undo", ":undo"]);
+    assert_eq!(session.world_name(), Some("second"));
+    assert_eq!(run(&mut session, &[":robots"]), vec!("*0: 0 1 N"));
+
+    run(&mut session, &[":undo", ":undo", ":undo"]);
+    assert_eq!(session.world_name(), Some("main"));
+    assert_eq!(run(&mut session, &[":worlds"]), vec!("*main 5x3 (1 robots)"));
+  }
+
+  #[test]
+  fn scripted_robot() {
+    let dir  = TestDir::new("session");
+    let path = dir.write("robot.rhai", r#"fn next_command(robot) { if robot.ahead == "free" { "F" } else { "" } }"#);
+
+    let mut session = Session::new(Validation::Strict);
+    run(&mut session, &["5 3", "0 1 E"]);
+
+    assert_eq!(session.handle_line(":run"), Err(SessionError::NoScript(String::from("0"))));
+    assert_eq!(run(&mut session, &[&format!(":script {}", path.display()), ":run"]), vec!("4 1 E"));
+    assert_eq!(run(&mut session, &[":undo", ":run 2"]), vec!("2 1 E"));
+    assert!(matches!(session.handle_line(":script /no/such/file.rhai"), Err(SessionError::Script(_))));
+  }
+
+  #[test]
+  fn programs() {
+    let mut session = Session::new(Validation::Strict);
+    let     output  = run(&mut session, &["5 3", "0 0 N", "WHILE NOT EDGE { F } R REPEAT 2 { F }", "2 0 N", "if occupied { L } else { R }"]);
+
+    assert_eq!(output, vec!("2 2 E", "2 0 E"));
+    assert_eq!( session.handle_line("REPEAT { F }").unwrap_err().to_string()
+              , "column 8: REPEAT must be followed by a positive whole number '{'\nREPEAT { F }\n       ^"
+              );
+
+    let reply = session.handle_line("WHILE NOT OCCUPIED { L R }").unwrap();
+    assert_eq!(reply.warnings, vec!("Robot 1 stopped after 10000 program steps"));
+  }
+
+  #[test]
+  fn energy_costs() {
+    let mut session = Session::new(Validation::Strict);
+
+    run(&mut session, &["5 3", ":costs 1 0", "0 0 N energy=3"]);
+    assert_eq!(run(&mut session, &[":costs"]), vec!("forward 1 turn 0"));
+
+    let reply = session.handle_line("RFFFF").unwrap();
+    assert_eq!(reply.output, vec!("3 0 E DEPLETED"));
+    assert_eq!(reply.warnings, vec!("Robot 0 is out of energy - ignored 1 command(s)"));
+
+    assert_eq!(session.handle_line(":costs 1"), Err(SessionError::InvalidWorld(ERROR_BAD_COSTS)));
+
+    // Recharge at a charging station
+    run(&mut session, &[":charger 4 0 2", "4 1 S energy=2"]);
+    assert_eq!(run(&mut session, &[":charger"]), vec!("4 0 +2"));
+    assert_eq!(run(&mut session, &["FLLF", "F"]), vec!("4 1 N", "4 2 N DEPLETED"));
+    assert_eq!(session.handle_line(":charger 5 0"), Err(SessionError::InvalidWorld(ERROR_BAD_CHARGER)));
+  }
+
+  #[test]
+  fn heavy_robots_push() {
+    let mut session = Session::new(Validation::Strict);
+    let     output  = run(&mut session, &["5 3", "1 1 N name=small", "1 0 N type=heavy", "F", ":robots"]);
+
+    assert_eq!(output, vec!("1 1 N (heavy)", " 0 small: 1 2 N", "*1: 1 1 N (heavy)"));
+  }
+
+  #[test]
+  fn cargo_is_lost_with_the_robot() {
+    let mut session = Session::new(Validation::Strict);
+
+    run(&mut session, &["5 3", ":item 1 1 crate", ":item 1 1 box", "1 1 N"]);
+
+    let reply = session.handle_line("PP").unwrap();
+    assert_eq!(reply.output, vec!("1 1 N carrying box"));
+    assert_eq!(reply.warnings, vec!("Robot 0 can't carry any more items"));
+
+    assert_eq!(run(&mut session, &["FFD", ":item"]), vec!("1 2 N LOST carrying box", "1 1 crate"));
+    assert_eq!(session.handle_line(":item 1 1"), Err(SessionError::InvalidWorld(ERROR_BAD_ITEM)));
+  }
+
+  #[test]
+  fn mission_goals() {
+    let mut session = Session::new(Validation::Strict);
+
+    run(&mut session, &["5 3", ":goal reach 3 1 by=scout points=2", ":goal survive", "1 1 E name=scout", "FF", "0 2 N", "F"]);
+
+    assert_eq!(run(&mut session, &[":goal"]), vec!("Goal 1: reach 3 1 by scout", "Goal 2: survive by all robots"));
+    assert_eq!( session.final_report()
+              , vec!( "World main:"
+                    , "Goal 1: reach 3 1 by scout (points: 2) PASS"
+                    , "Goal 2: survive by all robots (points: 1) FAIL"
+                    , "Score: 2/3 FAIL"
+                    )
+              );
+    assert_eq!(session.handle_line(":goal win"), Err(SessionError::InvalidGoal(PARSE_ERROR_BAD_GOAL)));
+
+    assert_eq!(run(&mut session, &[":reset scents", ":goal"]), vec!("Goal 1: reach 3 1 by scout", "Goal 2: survive by all robots"));
+  }
+
+  #[test]
+  fn lockstep_scheduling() {
+    let mut session = Session::new(Validation::Strict);
+
+    run(&mut session, &["5 3", ":schedule lockstep", "2 0 N", "WF", "0 1 E name=east", "FF"]);
+    assert_eq!(run(&mut session, &[":schedule"]), vec!("lockstep"));
+    assert_eq!(run(&mut session, &[":plans"]), vec!("0: WF", "east: FF"));
+    assert_eq!(session.robots()[1].to_string(), "east: 0 1 E");
+
+    // Both robots make for (2,1) on the second tick, but robot 0 goes first
+    let reply = session.handle_line(":go").unwrap();
+    assert_eq!(reply.output, vec!("2 1 N", "east: 1 1 E"));
+    assert_eq!(reply.warnings, vec!("Tick 2: robot east gave way to robot 0 at (2,1)"));
+    assert!(run(&mut session, &[":plans"]).is_empty());
+
+    assert!(matches!(session.handle_line("REPEAT 2 { F }"), Err(SessionError::InvalidInstructions(..))));
+    assert_eq!(session.handle_line(":schedule later"), Err(SessionError::InvalidWorld(ERROR_BAD_SCHEDULING)));
+    assert_eq!(run(&mut session, &[":undo", ":schedule sequential", ":select 0", "F"]), vec!("*0: 2 0 N", "2 1 N"));
+  }
+
+  #[test]
+  fn switch_validation() {
+    use crate::validate::PARSE_ERROR_BAD_VALIDATION;
+
+    let mut session = Session::new(Validation::Strict);
+    run(&mut session, &["5 3", "1 1 N"]);
+    assert!(matches!(session.handle_line("FXR"), Err(SessionError::InvalidInstructions(..))));
+    assert_eq!(run(&mut session, &[":validation"]), vec!("strict"));
+
+    run(&mut session, &[":validation LENIENT"]);
+    assert_eq!(run(&mut session, &[":validation"]), vec!("lenient"));
+    let reply = session.handle_line("FXR").unwrap();
+    assert_eq!(reply.output, vec!("1 2 E"));
+    assert_eq!(reply.warnings.len(), 1);
+
+    assert_eq!(session.handle_line(":validation loose"), Err(SessionError::InvalidWorld(PARSE_ERROR_BAD_VALIDATION)));
+  }
+
+  #[test]
+  fn lockstep_deadlock() {
+    let mut session = Session::new(Validation::Strict);
+
+    run(&mut session, &["5 3", ":schedule lockstep", "1 1 E", "FF", "2 1 W name=west", "FF"]);
+
+    let reply = session.handle_line(":go").unwrap();
+    assert_eq!(reply.output, vec!("1 1 E", "west: 2 1 W"));
+    assert_eq!(reply.warnings, vec!( "Robot 0 can't move to (2,1) - location already occupied"
+                                   , "Robot 0 can't move to (2,1) - location already occupied"
+                                   , "Robot west can't move to (1,1) - location already occupied"
+                                   , "Robot west can't move to (1,1) - location already occupied"
+                                   , "Tick 1: deadlock - robots 0, west are blocking each other"
+                                   ));
+  }
+
+  #[test]
+  fn coordinate_conventions() {
+    let mut session = Session::new(Validation::Strict);
+
+    // (1,1) is the top-left location
+    run(&mut session, &[":coords 1 top-left", "5 3", ":charger 2 3", ":goal reach 3 1", "1 1 E"]);
+    assert_eq!(session.robots()[0].status(), "0 2 E");
+    assert_eq!(run(&mut session, &["FF"]), vec!("3 1 E"));
+    assert_eq!(run(&mut session, &["5 1 E", "F", ":scents"]), vec!("5 1 E LOST", "5 1 E"));
+    assert_eq!(run(&mut session, &[":charger", ":goal", ":coords"]), vec!("2 3 +1", "Goal 1: reach 3 1 by all robots", "1 top-left"));
+    assert_eq!( run(&mut session, &[":select 0", "?FFF"])
+              , vec!("*0: 3 1 E", "Would finish at 5 1 E\n  Step 3: skipped heading E from (5,1) - here be monsters")
+              );
+
+    assert_eq!(session.handle_line(":coords 2 middle"), Err(SessionError::InvalidWorld(PARSE_ERROR_BAD_COORDS)));
+    assert_eq!(session.handle_line("6 1 E"), Err(SessionError::InvalidRobot("Cannot create robot - location lies outside this world's boundaries")));
+  }
+
+  #[test]
+  fn classic_dialect() {
+    let mut session = Session::new(Validation::Strict).with_dialect(Dialect::Classic);
+
+    // The upper-right location is (5,3), so the world is 6 wide and 4 high
+    assert_eq!( run(&mut session, &["5 3", "1 1 E", "RFRFRFRF", "3 2 N", "FRRFLLFFRRFLL", "0 3 W", "LLFFFLFLFL"])
+              , vec!("1 1 E", "3 3 N LOST", "2 3 S")
+              );
+    assert_eq!(session.world().map(|w| (w.width, w.height)), Some((6, 4)));
+
+    assert_eq!(run(&mut session, &[":dialect", ":dialect native", ":new second 5 3", ":dialect"]), vec!("classic", "native"));
+    assert_eq!(session.world().map(|w| (w.width, w.height)), Some((5, 3)));
+    assert_eq!(session.handle_line(":dialect martian"), Err(SessionError::InvalidWorld(PARSE_ERROR_BAD_DIALECT)));
+  }
+
+  #[test]
+  fn limits_and_rules_belong_to_the_session() {
+    use crate::rules::Collisions;
+
+    let limits      = Limits::new(80, 20).unwrap();
+    let rules       = Rules { collisions : Collisions::Halt, ..Rules::default() };
+    let mut roomy   = Session::new(Validation::Strict).with_limits(limits).with_rules(rules);
+    let mut cramped = Session::new(Validation::Strict);
+
+    run(&mut roomy, &["80 20", ":new other 3 3"]);
+    assert_eq!(roomy.world().map(|w| w.rules), Some(rules));
+    assert_eq!(roomy.handle_line(":new huge 81 20"), Err(SessionError::InvalidDimensions(DimensionsError::OutOfRange(limits))));
+
+    assert_eq!(cramped.handle_line("80 20"), Err(SessionError::InvalidDimensions(DimensionsError::OutOfRange(Limits::DEFAULT))));
+    run(&mut cramped, &["5 3"]);
+    assert_eq!(cramped.world().map(|w| w.rules), Some(Rules::default()));
+  }
+
+  #[test]
+  fn obstacles() {
+    let mut session = Session::new(Validation::Strict);
+
+    run(&mut session, &["5 3", ":obstacle 2 1", "0 1 E"]);
+    assert_eq!(session.handle_line("FF"), Ok(Reply { output   : vec!("1 1 E".to_string())
+                                                   , warnings : vec!("Robot 0 can't move to (2,1) - location blocked by an obstacle".to_string())
+                                                   }));
+    assert_eq!(run(&mut session, &[":obstacle", "IF OCCUPIED { L } ELSE { F }"]), vec!("2 1", "1 1 N"));
+    assert_eq!(session.handle_line("2 1 N"), Err(SessionError::InvalidRobot("Cannot create robot - location blocked by an obstacle")));
+    assert_eq!(session.handle_line(":obstacle 1 1"), Err(SessionError::InvalidWorld(ERROR_BAD_OBSTACLE)));
+    assert_eq!(session.handle_line(":obstacle 5 1"), Err(SessionError::InvalidWorld(ERROR_BAD_OBSTACLE)));
+
+    // Obstacles outlast the robots
+    assert_eq!(run(&mut session, &[":reset", ":obstacle"]), vec!("2 1"));
+
+    // A hopper that gets part of the way hasn't stayed put
+    run(&mut session, &["0 1 E type=hopper"]);
+    assert_eq!(session.handle_line("F"), Ok(Reply { output   : vec!("1 1 E (hopper)".to_string())
+                                                  , warnings : vec!("Robot 0 stopped short of (2,1) - location blocked by an obstacle".to_string())
+                                                  }));
+  }
+
+  #[test]
+  fn every_meta_command_in_the_help_is_known() {
+    let mut session = Session::new(Validation::Strict);
+
+    for cmd in HELP_TEXT.lines().filter_map(|line| line.split_ascii_whitespace().next()).filter(|w| w.starts_with(META_PREFIX)) {
+      assert!(!matches!(session.handle_line(cmd), Err(SessionError::UnknownCommand(_))), "{}", cmd);
+    }
+  }
+
+  #[test]
+  fn instructions_need_a_robot() {
+    let mut session = Session::new(Validation::Strict);
+
+    assert_eq!(session.handle_line(":show"), Err(SessionError::NoWorld));
+    run(&mut session, &["5 3"]);
+    assert_eq!(session.handle_line("F"), Err(SessionError::NoRobotSelected));
+    assert!(session.handle_line("1 1 E").is_ok());
+    assert!(matches!(session.handle_line("FX"), Err(SessionError::InvalidInstructions(..))));
+  }
+}
@@ -0,0 +1,49 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Tests run in parallel, so every directory needs a name of its own
+static NEXT_DIR : AtomicUsize = AtomicUsize::new(0);
+
+// *********************************************************************************************************************
+// A scratch directory for tests that read files, removed again however the test ends
+// *********************************************************************************************************************
+pub struct TestDir {
+  path : PathBuf
+}
+
+impl TestDir {
+  pub fn new(name : &str) -> TestDir {
+    let path = std::env::temp_dir().join(format!( "rusty_robots_{}_{}_{}"
+                                                , name, std::process::id(), NEXT_DIR.fetch_add(1, Ordering::SeqCst)
+                                                ));
+    fs::create_dir_all(&path).unwrap();
+    TestDir { path }
+  }
+
+  pub fn path(&self) -> &Path {
+    &self.path
+  }
+
+  pub fn join(&self, file : &str) -> PathBuf {
+    self.path.join(file)
+  }
+
+  // Write a file somewhere below the directory, creating any subdirectories on the way
+  pub fn write(&self, file : &str, text : &str) -> PathBuf {
+    let path = self.join(file);
+
+    if let Some(parent) = path.parent() {
+      fs::create_dir_all(parent).unwrap();
+    }
+
+    fs::write(&path, text).unwrap();
+    path
+  }
+}
+
+impl Drop for TestDir {
+  fn drop(&mut self) {
+    let _ = fs::remove_dir_all(&self.path);
+  }
+}
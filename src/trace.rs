@@ -3,15 +3,71 @@
 // 
 // (c) Chris Whealy 2019
 // *********************************************************************************************************************
+use std::{fmt, str};
+use std::sync::atomic::{AtomicU8, Ordering};
 
-const ENTRY_ARROW  : &str = &"--->";
-const EXIT_ARROW   : &str = &"<---";
-const IN_OUT_ARROW : &str = &"<-->";
+const ENTRY_ARROW  : &str = "--->";
+const EXIT_ARROW   : &str = "<---";
+const IN_OUT_ARROW : &str = "<-->";
+
+pub const PARSE_ERROR_BAD_TRACE_LEVEL : &str = "Trace level must be one of off, calls or all";
+
+// Tracing for every module, on top of whatever each module's own TRACE_ACTIVE switches on
+static LEVEL : AtomicU8 = AtomicU8::new(TraceLevel::Off as u8);
+
+// *********************************************************************************************************************
+// How much to trace
+// *********************************************************************************************************************
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Default)]
+pub enum TraceLevel {
+  #[default]
+  Off         // Only what each module switches on for itself
+, Calls       // Function boundaries
+, All         // Function boundaries and data
+}
+
+impl fmt::Display for TraceLevel {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      TraceLevel::Off   => write!(f, "off")
+    , TraceLevel::Calls => write!(f, "calls")
+    , TraceLevel::All   => write!(f, "all")
+    }
+  }
+}
+
+impl str::FromStr for TraceLevel {
+  type Err = &'static str;
+
+  fn from_str(s: &str) -> Result<TraceLevel, Self::Err> {
+    match s.trim().to_ascii_lowercase().as_str() {
+      "off"   => Ok(TraceLevel::Off)
+    , "calls" => Ok(TraceLevel::Calls)
+    , "all"   => Ok(TraceLevel::All)
+    , _       => Err(PARSE_ERROR_BAD_TRACE_LEVEL)
+    }
+  }
+}
 
 
 pub struct Trace {}
 
 impl Trace {
+  // *******************************************************************************************************************
+  // The trace level for every module
+  // *******************************************************************************************************************
+  pub fn level() -> TraceLevel {
+    match LEVEL.load(Ordering::Relaxed) {
+      0 => TraceLevel::Off
+    , 1 => TraceLevel::Calls
+    , _ => TraceLevel::All
+    }
+  }
+
+  pub fn set_level(level : TraceLevel) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+  }
+
   // *******************************************************************************************************************
   // Trace execution flow at function boundaries
   // *******************************************************************************************************************
@@ -22,16 +78,13 @@ impl Trace {
   ) -> impl Fn(&'a Option<bool>)
   {
     move |is_entry| {
-      if *is_active {
+      if *is_active || Trace::level() >= TraceLevel::Calls {
         let ptr = match is_entry {
           Some(b) => if *b { ENTRY_ARROW } else { EXIT_ARROW }
         , None    => IN_OUT_ARROW
         };
 
-        &println!("{} {}.{}()", ptr, lib_name, fn_name);
-      }
-      else {
-        ()
+        eprintln!("{} {}.{}()", ptr, lib_name, fn_name);
       }
     }
   }
@@ -46,11 +99,8 @@ impl Trace {
   ) -> impl Fn(&str) + 'a 
   {
     move |info| {
-      if *is_active {
-        &println!("     {}.{}() {}", lib_name, fn_name, info);
-      }
-      else {
-        ()
+      if *is_active || Trace::level() == TraceLevel::All {
+        eprintln!("     {}.{}() {}", lib_name, fn_name, info);
       }
     }
   }
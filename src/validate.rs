@@ -0,0 +1,155 @@
+use std::{fmt, str};
+
+use crate::trace::Trace;
+
+const LIB_NAME     : &str  = module_path!();
+const TRACE_ACTIVE : &bool = &false;
+
+pub const VALID_COMMANDS : [char; 6] = ['F', 'L', 'R', 'W', 'P', 'D'];
+
+pub const DIAG_NO_INSTRUCTIONS : &str = "No move/turn instructions found";
+pub const DIAG_INVALID_COMMAND : &str = "Invalid move/turn command";
+pub const DIAG_TRAILING_TEXT   : &str = "Unexpected text after move/turn instructions";
+
+pub const PARSE_ERROR_BAD_VALIDATION : &str = "Validation must be either strict or lenient";
+
+// *********************************************************************************************************************
+// How fussy should we be about instruction lines?
+// *********************************************************************************************************************
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Validation {
+  Strict      // Reject the whole line if anything is wrong with it
+, Lenient     // Warn, then obey whatever commands are valid
+}
+
+impl fmt::Display for Validation {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Validation::Strict  => write!(f, "strict")
+    , Validation::Lenient => write!(f, "lenient")
+    }
+  }
+}
+
+impl str::FromStr for Validation {
+  type Err = &'static str;
+
+  fn from_str(s: &str) -> Result<Validation, Self::Err> {
+    match s.trim().to_ascii_lowercase().as_str() {
+      "strict"  => Ok(Validation::Strict)
+    , "lenient" => Ok(Validation::Lenient)
+    , _         => Err(PARSE_ERROR_BAD_VALIDATION)
+    }
+  }
+}
+
+// *********************************************************************************************************************
+// Diagnostic definition
+// *********************************************************************************************************************
+#[derive(Debug, PartialEq, Clone)]
+pub struct Diagnostic {
+  pub column  : usize           // One-based character position within the instruction line
+, pub found   : Option<char>    // The offending character (if any)
+, pub message : &'static str
+}
+
+impl fmt::Display for Diagnostic {
+  fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+    match self.found {
+      Some(c) => write!(fmt, "column {}: {} '{}'", self.column, self.message, c)
+    , None    => write!(fmt, "column {}: {}", self.column, self.message)
+    }
+  }
+}
+
+// *********************************************************************************************************************
+// Split a line of move/turn instructions into the valid commands and a list of everything wrong with it
+// *********************************************************************************************************************
+pub fn check_instructions(line : &str) -> (String, Vec<Diagnostic>) {
+  const FN_NAME : &str = "check_instructions";
+
+  let trace_boundary = Trace::make_boundary_trace_fn(TRACE_ACTIVE, LIB_NAME, FN_NAME);
+  let trace          = Trace::make_trace_fn(TRACE_ACTIVE, LIB_NAME, FN_NAME);
+
+  trace_boundary(&Some(true));
+
+  let mut cmds  : String          = String::new();
+  let mut diags : Vec<Diagnostic> = vec!();
+  let mut chars = line.chars().enumerate().skip_while(|(_, c)| c.is_ascii_whitespace()).peekable();
+
+  if chars.peek().is_none() {
+    diags.push(Diagnostic { column : line.chars().count() + 1, found : None, message : DIAG_NO_INSTRUCTIONS });
+  }
+
+  // The first whitespace delimited token contains the commands
+  while let Some((idx, c)) = chars.next() {
+    if c.is_ascii_whitespace() {
+      // Anything after that is an error
+      if let Some((idx, c)) = chars.find(|(_, c)| !c.is_ascii_whitespace()) {
+        diags.push(Diagnostic { column : idx + 1, found : Some(c), message : DIAG_TRAILING_TEXT });
+      }
+
+      break;
+    }
+
+    let cmd = c.to_ascii_uppercase();
+
+    if VALID_COMMANDS.contains(&cmd) {
+      cmds.push(cmd);
+    }
+    else {
+      diags.push(Diagnostic { column : idx + 1, found : Some(c), message : DIAG_INVALID_COMMAND });
+    }
+  }
+
+  trace(&format!("Found {} valid commands and {} problems", cmds.len(), diags.len()));
+  trace_boundary(&Some(false));
+
+  (cmds, diags)
+}
+
+// *********************************************************************************************************************
+// Underline the problems in an instruction line
+// *********************************************************************************************************************
+pub fn mark_diagnostics(line : &str, diags : &[Diagnostic]) -> String {
+  let width   = diags.iter().map(|d| d.column).max().unwrap_or(0);
+  let markers : String = (1..=width).map(|col| if diags.iter().any(|d| d.column == col) { '^' } else { ' ' }).collect();
+
+  format!("{}\n{}", line, markers)
+}
+
+// *********************************************************************************************************************
+// Suppose we'd better test it...
+// *********************************************************************************************************************
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn valid_instructions() {
+    assert_eq!(check_instructions("FRL"),    (String::from("FRL"), vec!()));
+    assert_eq!(check_instructions("  frl "), (String::from("FRL"), vec!()));
+  }
+
+  #[test]
+  fn find_invalid_commands() {
+    let (cmds, diags) = check_instructions("FXRq");
+
+    assert_eq!(cmds, "FR");
+    assert_eq!(diags, vec!(
+      Diagnostic { column : 2, found : Some('X'), message : DIAG_INVALID_COMMAND }
+    , Diagnostic { column : 4, found : Some('q'), message : DIAG_INVALID_COMMAND }
+    ));
+    assert_eq!(mark_diagnostics("FXRq", &diags), "FXRq\n ^ ^");
+  }
+
+  #[test]
+  fn find_trailing_text_and_empty_lines() {
+    assert_eq!( check_instructions("FF  LR")
+              , (String::from("FF"), vec!(Diagnostic { column : 5, found : Some('L'), message : DIAG_TRAILING_TEXT }))
+              );
+    assert_eq!( check_instructions("  ")
+              , (String::new(), vec!(Diagnostic { column : 3, found : None, message : DIAG_NO_INSTRUCTIONS }))
+              );
+  }
+}
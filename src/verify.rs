@@ -0,0 +1,315 @@
+use std::{fs, str};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::program::is_program;
+use crate::robot::Robot;
+use crate::rules::Rules;
+use crate::scenario::{Block, Meta, MetaCommand, RobotDecl, Statement, WorldDecl};
+use crate::session::{Session, SessionError};
+use crate::validate::Validation;
+use crate::world::{Dialect, Limits};
+use crate::trace::Trace;
+
+const LIB_NAME     : &str  = module_path!();
+const TRACE_ACTIVE : &bool = &false;
+
+pub const SCENARIO_EXTENSION : &str = "toml";
+
+const RESULT_PASS  : &str = "PASS";
+const RESULT_FAIL  : &str = "FAIL";
+const RESULT_ERROR : &str = "ERROR";
+
+// *********************************************************************************************************************
+// Declarative scenario definition
+//
+//   name = "The classic sample"       # Optional
+//
+//   [world]
+//   width     = 5
+//   height    = 3
+//   obstacles = [[2, 2], [4, 0]]      # Optional
+//
+//   [[robots]]
+//   start        = "1 1 E"            # A robot definition, exactly as it would be typed
+//   instructions = "RFRFRFRF"         # Optional
+//   expect       = "1 1 E"            # The robot's report once it has obeyed its instructions
+//
+// The robots are created and obey their instructions one after the other, as in the classic problem
+// *********************************************************************************************************************
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Spec {
+  pub name   : Option<String>
+, pub world  : WorldSpec
+, pub robots : Vec<RobotSpec>
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WorldSpec {
+  pub width     : i32
+, pub height    : i32
+, #[serde(default)]
+  pub obstacles : Vec<(i32, i32)>
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RobotSpec {
+  pub start        : String
+, #[serde(default)]
+  pub instructions : String
+, pub expect       : String
+}
+
+impl str::FromStr for Spec {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Spec, Self::Err> {
+    toml::from_str(s).map_err(|err| err.to_string().trim_end().to_string())
+  }
+}
+
+// *********************************************************************************************************************
+// How one robot fared against what was expected of it
+// *********************************************************************************************************************
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Check {
+  pub robot    : usize      // 1-based, in the order the robots are listed
+, pub start    : String
+, pub expected : String
+, pub actual   : String     // The robot's report, or the error that stopped it being created or obeying
+}
+
+impl Check {
+  pub fn passed(&self) -> bool {
+    self.actual == self.expected
+  }
+}
+
+// *********************************************************************************************************************
+// The result of verifying a scenario file
+// *********************************************************************************************************************
+#[derive(Debug, Serialize)]
+pub enum Verdict {
+  Checked(Vec<Check>)
+, Invalid(String)         // The file could not be read, is not a valid scenario, or its world could not be set up
+}
+
+#[derive(Debug, Serialize)]
+pub struct Verification {
+  pub path    : PathBuf
+, pub name    : Option<String>
+, pub verdict : Verdict
+}
+
+impl Verification {
+  pub fn passed(&self) -> bool {
+    matches!(&self.verdict, Verdict::Checked(checks) if checks.iter().all(Check::passed))
+  }
+}
+
+// *********************************************************************************************************************
+// Spec implementation
+// *********************************************************************************************************************
+impl Spec {
+  // Feed the scenario to a session of its own, as the statements it would have been typed in as.  Nothing can be
+  // checked if the world can't be set up
+  pub fn run(&self, validation : Validation, limits : Limits, rules : Rules) -> Result<Vec<Check>, String> {
+    const FN_NAME : &str = "run";
+
+    let trace_boundary = Trace::make_boundary_trace_fn(TRACE_ACTIVE, LIB_NAME, FN_NAME);
+    let trace          = Trace::make_trace_fn(TRACE_ACTIVE, LIB_NAME, FN_NAME);
+
+    trace_boundary(&Some(true));
+
+    let mut session = Session::new(validation).with_limits(limits).with_rules(rules);
+
+    if let Err(err) = self.world.statements(&limits).and_then(|setup| handle_all(&mut session, &setup)) {
+      trace_boundary(&Some(false));
+      return Err(format!("Cannot set up the world: {}", err))
+    }
+
+    let checks = self.robots.iter()
+      .enumerate()
+      .map(|(idx, spec)| {
+        let actual = run_robot(&mut session, spec);
+
+        trace(&format!("Robot {}: expected '{}', got '{}'", idx + 1, spec.expect, actual));
+        Check { robot : idx + 1, start : spec.start.clone(), expected : spec.expect.trim().to_string(), actual }
+      })
+      .collect();
+
+    trace_boundary(&Some(false));
+    Ok(checks)
+  }
+}
+
+impl WorldSpec {
+  // The world's size, then each of its obstacles
+  fn statements(&self, limits : &Limits) -> Result<Vec<Statement>, SessionError> {
+    let     dims       = limits.dimensions(self.width, self.height).map_err(SessionError::InvalidDimensions)?;
+    let mut statements = vec!(Statement::World(WorldDecl { name : None, dims, dialect : Dialect::Native }));
+
+    statements.extend(self.obstacles.iter().map(|(x, y)|
+      Statement::Meta(MetaCommand { name : Meta::Obstacle, args : vec!(x.to_string(), y.to_string()) })
+    ));
+    Ok(statements)
+  }
+}
+
+impl RobotSpec {
+  // The robot, then its instructions if it has any
+  fn statements(&self) -> Result<Vec<Statement>, SessionError> {
+    let     robot        = self.start.trim().parse::<Robot>().map_err(SessionError::InvalidRobot)?;
+    let mut statements   = vec!(Statement::Robot(RobotDecl { robot }));
+    let     instructions = self.instructions.trim();
+
+    if !instructions.is_empty() {
+      statements.push(Statement::Instructions(Block { text : instructions.to_string(), is_program : is_program(instructions) }));
+    }
+    Ok(statements)
+  }
+}
+
+// *********************************************************************************************************************
+// Verify a scenario file
+// *********************************************************************************************************************
+pub fn verify_file(path : &Path, validation : Validation, limits : Limits, rules : Rules) -> Verification {
+  let spec = fs::read_to_string(path)
+    .map_err(|err| format!("Cannot read {}: {}", path.display(), err))
+    .and_then(|text| text.parse::<Spec>());
+
+  let (name, verdict) = match spec {
+    Ok(spec) => match spec.run(validation, limits, rules) {
+      Ok(checks) => (spec.name, Verdict::Checked(checks))
+    , Err(err)   => (spec.name, Verdict::Invalid(err))
+    }
+  , Err(err) => (None, Verdict::Invalid(err))
+  };
+
+  Verification { path : path.to_path_buf(), name, verdict }
+}
+
+// *********************************************************************************************************************
+// One line per file, each robot that didn't do as expected shown as a diff, then the totals
+// *********************************************************************************************************************
+pub fn report(verifications : &[Verification]) -> Vec<String> {
+  let mut lines = vec!();
+
+  for v in verifications {
+    let title = match &v.name {
+      Some(name) => format!("{} ({})", v.path.display(), name)
+    , None       => v.path.display().to_string()
+    };
+
+    match &v.verdict {
+      Verdict::Invalid(err) => {
+        lines.push(format!("{}  {}", RESULT_ERROR, title));
+        lines.extend(err.lines().map(|line| format!("    {}", line)));
+      }
+    , Verdict::Checked(checks) => {
+        let passed = checks.iter().filter(|c| c.passed()).count();
+        let result = if passed == checks.len() { RESULT_PASS } else { RESULT_FAIL };
+
+        lines.push(format!("{}  {}  {}/{} robot(s)", result, title, passed, checks.len()));
+
+        for check in checks.iter().filter(|c| !c.passed()) {
+          lines.push(format!("    robot {} ({})", check.robot, check.start));
+          lines.push(format!("    - {}", check.expected));
+          lines.push(format!("    + {}", check.actual));
+        }
+      }
+    }
+  }
+
+  let passed = verifications.iter().filter(|v| v.passed()).count();
+  lines.push(format!("{} file(s): {} passed, {} failed", verifications.len(), passed, verifications.len() - passed));
+  lines
+}
+
+// *********************************************************************************************************************
+// Private API
+// *********************************************************************************************************************
+fn run_robot(session : &mut Session, spec : &RobotSpec) -> String {
+  if let Err(err) = spec.statements().and_then(|statements| handle_all(session, &statements)) {
+    return format!("Error: {}", err)
+  }
+
+  match (session.selected(), session.world()) {
+    (Some(robot), Some(world)) => robot.report(world)
+  , _                          => String::new()
+  }
+}
+
+// Stop at the first statement the session won't accept
+fn handle_all(session : &mut Session, statements : &[Statement]) -> Result<(), SessionError> {
+  statements.iter().try_for_each(|statement| session.handle_statement(statement).map(drop))
+}
+
+// *********************************************************************************************************************
+// Suppose we'd better test it...
+// *********************************************************************************************************************
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const CLASSIC : &str = r#"
+name = "classic"
+
+[world]
+width     = 6
+height    = 4
+obstacles = [[5, 0]]
+
+[[robots]]
+start        = "1 1 E"
+instructions = "RFRFRFRF"
+expect       = "1 1 E"
+
+[[robots]]
+start        = "3 2 N"
+instructions = "FRRFLLFFRRFLL"
+expect       = "3 2 N LOST"
+
+[[robots]]
+start        = "5 0 N"
+expect       = "5 0 N"
+"#;
+
+  #[test]
+  fn verify_expected_reports() {
+    let checks = CLASSIC.parse::<Spec>().unwrap().run(Validation::Strict, Limits::DEFAULT, Rules::default()).unwrap();
+
+    assert_eq!(checks.iter().map(Check::passed).collect::<Vec<bool>>(), vec!(true, false, false));
+    assert_eq!(checks[1].actual, "3 3 N LOST");
+    assert_eq!(checks[2].actual, "Error: Cannot create robot - location blocked by an obstacle");
+
+    let verification = Verification { path : PathBuf::from("classic.toml"), name : Some(String::from("classic")), verdict : Verdict::Checked(checks) };
+    assert_eq!( report(&[verification])
+              , vec!( "FAIL  classic.toml (classic)  1/3 robot(s)"
+                    , "    robot 2 (3 2 N)"
+                    , "    - 3 2 N LOST"
+                    , "    + 3 3 N LOST"
+                    , "    robot 3 (5 0 N)"
+                    , "    - 5 0 N"
+                    , "    + Error: Cannot create robot - location blocked by an obstacle"
+                    , "1 file(s): 0 passed, 1 failed"
+                    )
+              );
+  }
+
+  #[test]
+  fn reject_bad_specs() {
+    assert!("[world]\nwidth = 5\n".parse::<Spec>().is_err());
+    assert!("[world]\nwidth = 5\nheight = 3\nholes = []\n".parse::<Spec>().unwrap_err().contains("unknown field"));
+
+    let spec = "robots = []\n[world]\nwidth = 0\nheight = 3\n".parse::<Spec>().unwrap();
+    assert_eq!(spec.run(Validation::Strict, Limits::DEFAULT, Rules::default()), Err(String::from("Cannot set up the world: Both world dimensions must be in the range 1 to 50")));
+
+    let spec = "robots = []\n[world]\nwidth = 5\nheight = 3\nobstacles = [[5, 0]]\n".parse::<Spec>().unwrap();
+    assert_eq!(spec.run(Validation::Strict, Limits::DEFAULT, Rules::default()), Err(String::from("Cannot set up the world: An obstacle needs an empty location inside this world")));
+  }
+}
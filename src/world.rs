@@ -1,10 +1,11 @@
 use std::{str, fmt};
 use std::vec::Vec;
-use std::io::prelude::*;
-use std::io::BufReader;
+use std::collections::BTreeMap;
 
+use crate::coords::Coords;
 use crate::location::Location;
 use crate::heading::Heading;
+use crate::rules::{Rules, ScentMode};
 use crate::trace::Trace;
 
 const LIB_NAME     : &str  = module_path!();
@@ -15,27 +16,68 @@ pub const WORLD_MIN_HEIGHT : i32 = 1;
 pub const WORLD_MAX_WIDTH  : i32 = 50;
 pub const WORLD_MAX_HEIGHT : i32 = 50;
 
-const PARSE_ERROR_MISSING_DIMS : &str = &"Please specify world dimensions";
-const PARSE_ERROR_MISSING_DIM  : &str = &"Expecting two world dimensions, only found one";
-const PARSE_ERROR_BAD_WIDTH    : &str = &"World width must be an integer";
-const PARSE_ERROR_BAD_HEIGHT   : &str = &"World height must be an integer";
+// No limit may be raised beyond this
+pub const WORLD_LIMIT_CEILING : i32 = 1000;
 
-const ERROR_INVALID_WORLD_DIMS : &str = &"Both world dimensions must be in the range 1 to 50";
+const PARSE_ERROR_MISSING_DIMS : &str = "Please specify world dimensions";
+const PARSE_ERROR_MISSING_DIM  : &str = "Expecting two world dimensions, only found one";
+const PARSE_ERROR_BAD_WIDTH    : &str = "World width must be an integer";
+const PARSE_ERROR_BAD_HEIGHT   : &str = "World height must be an integer";
 
-pub const PROMPT_NEW_WORLD : &str = &"Enter width and height of world";
-pub const EOF_ENCOUNTERED  : &str = &"EOF stdin";
+pub const PARSE_ERROR_BAD_DIALECT : &str = "Input dialect must be either native or classic";
+pub const PARSE_ERROR_BAD_LIMITS  : &str = "World limits must be in the range 1 to 1000";
 
-const FORMAT_CHAR_VERT  : &str = &"|";
-const FORMAT_CHAR_HORIZ : &str = &"-";
+pub const PROMPT_NEW_WORLD : &str = "Enter width and height of world";
+
+const FORMAT_CHAR_VERT  : &str = "|";
+const FORMAT_CHAR_HORIZ : &str = "-";
+const FORMAT_CHARGER    : &str = "+";
+const FORMAT_ITEMS      : &str = "*";
+const FORMAT_OBSTACLE   : &str = "#";
+
+pub const DEFAULT_CHARGE_RATE : u32 = 1;
+
+// *********************************************************************************************************************
+// What lies one step away from a location
+// *********************************************************************************************************************
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Outlook {
+  Free            // A vacant location inside the world
+, Occupied(i32)   // A location occupied by the robot with this id
+, Edge            // The edge of the world
+, Scent           // The edge of the world, where a previous robot was lost
+, Obstacle        // A location blocked by an obstacle
+}
+
+// *********************************************************************************************************************
+// How much energy each move/turn command uses
+// *********************************************************************************************************************
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct EnergyCosts {
+  pub forward : u32
+, pub turn    : u32
+}
+
+impl Default for EnergyCosts {
+  fn default() -> EnergyCosts {
+    EnergyCosts { forward : 1, turn : 1 }
+  }
+}
 
 // *********************************************************************************************************************
 // World definition
 // *********************************************************************************************************************
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct World {
   pub width     : i32
 , pub height    : i32
 , pub locations : Vec<Location>
+, pub roster    : BTreeMap<i32, Option<String>>    // Every robot ever placed in this world, lost or not
+, pub costs     : EnergyCosts                       // Only robots with an energy budget pay these
+, pub coords    : Coords                            // How coordinates are read and written
+, pub rules     : Rules                             // What happens on collisions, and whether lost robots leave a scent
+, chargers      : BTreeMap<usize, u32>              // Charge rate by location index
+, items         : BTreeMap<usize, Vec<String>>      // Items by location index, the last one on top
 }
 
 // *********************************************************************************************************************
@@ -43,56 +85,160 @@ pub struct World {
 // *********************************************************************************************************************
 impl fmt::Display for World {
   fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-    let _ = write!(fmt, "\n");
+    let _ = writeln!(fmt);
+
+    // Under any other than the default convention, rows and columns are labelled with their coordinates
+    let labelled = self.coords != Coords::default();
+    let (row_labels, col_labels) : (Vec<String>, Vec<String>) = if labelled {
+      ( (0..self.height).map(|y| self.to_external(&0, &y).1.to_string()).collect()
+      , (0..self.width).map(|x| self.to_external(&x, &0).0.to_string()).collect()
+      )
+    }
+    else {
+      (vec!(), vec!())
+    };
+    let margin = if labelled { " ".repeat(row_labels.iter().map(String::len).max().unwrap_or(0) + 1) } else { String::new() };
+
+    // Each cell must be wide enough for the longest robot name or id, and for its column label
+    let cell_width = self.roster.keys().map(|id| self.robot_label(id).len())
+                         .chain(col_labels.iter().map(String::len))
+                         .max()
+                         .unwrap_or(1);
 
     // Write top line
-    write_horiz_line(fmt, &self.width);
+    write_horiz_line(fmt, &margin, &self.width, cell_width);
 
     for i in (0..self.height).rev() {
+      if labelled {
+        let _ = write!(fmt, "{:>w$} ", row_labels[i as usize], w = margin.len() - 1);
+      }
+
       for j in 0..self.width {
         let idx = index_from_x_y(&self.width, &j, &i);
-        let this_loc : &Location = &self.locations[idx];
-        let id : &str = &this_loc.id.to_string();
-        let _ = write!(fmt, "{} {} ", FORMAT_CHAR_VERT, if &this_loc.id == &-1 { &" " } else { id });
+        let label = match self.locations[idx].occupant() {
+          _ if self.locations[idx].is_obstacle()   => String::from(FORMAT_OBSTACLE)
+        , Some(id)                                 => self.robot_label(&id)
+        , None if self.items.contains_key(&idx)    => String::from(FORMAT_ITEMS)
+        , None if self.chargers.contains_key(&idx) => String::from(FORMAT_CHARGER)
+        , None                                     => String::from(" ")
+        };
+        let _ = write!(fmt, "{} {:^w$} ", FORMAT_CHAR_VERT, label, w = cell_width);
       }
 
       // Write line terminator format character
       let _ = writeln!(fmt, "{}", FORMAT_CHAR_VERT);
     }
 
-    // Write bottom line
-    write_horiz_line(fmt, &self.width);
+    // Write bottom line, then any column labels
+    write_horiz_line(fmt, &margin, &self.width, cell_width);
+
+    if labelled {
+      let labels : String = col_labels.iter().map(|label| format!("  {:^w$} ", label, w = cell_width)).collect();
+      let _ = writeln!(fmt, "{}{}", margin, labels.trim_end());
+    }
 
     Ok(())
   }
 }
 
 impl World {
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  // Who's who?
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  pub fn enrol_robot(&mut self, robot_id : &i32, name : &Option<String>) {
+    self.roster.insert(*robot_id, name.clone());
+  }
+
+  pub fn has_robot_id(&self, robot_id : &i32) -> bool {
+    self.roster.contains_key(robot_id)
+  }
+
+  pub fn has_robot_name(&self, name : &str) -> bool {
+    self.roster.values().any(|n| n.as_deref() == Some(name))
+  }
+
+  // None once the highest possible id has been used
+  pub fn next_robot_id(&self) -> Option<i32> {
+    self.roster.keys().next_back().map_or(Some(0), |id| id.checked_add(1))
+  }
+
+  pub fn robot_label(&self, robot_id : &i32) -> String {
+    match self.roster.get(robot_id) {
+      Some(Some(name)) => name.clone()
+    , _                => robot_id.to_string()
+    }
+  }
+
+  // Where is this robot now?
+  pub fn locate(&self, robot_id : &i32) -> Option<(i32, i32)> {
+    self.locations.iter().position(|loc| loc.is_occupied_by(*robot_id)).map(|idx| x_y_from_index(&self.width, idx))
+  }
+
   pub fn is_location_occupied(&self, x : &i32, y : &i32) -> bool {
-    &self.locations[index_from_x_y(&self.width, &x, &y)].id != &-1
+    self.locations[index_from_x_y(&self.width, x, y)].occupant().is_some()
   }
 
   pub fn place_robot_at(&mut self, robot_id : &i32, x : &i32, y : &i32) {
-    Trace::make_trace_fn(TRACE_ACTIVE, LIB_NAME, &"place_robot_at")(&format!("Robot id {} now occupies location ({},{})", &robot_id, &x, &y));
-    self.locations[index_from_x_y(&self.width, &x, &y)].id = *robot_id;
+    Trace::make_trace_fn(TRACE_ACTIVE, LIB_NAME, "place_robot_at")(&format!("Robot id {} now occupies location ({},{})", &robot_id, &x, &y));
+    self.locations[index_from_x_y(&self.width, x, y)].occupy(*robot_id);
   }
 
   pub fn remove_robot_from(&mut self, x : &i32, y : &i32) {
-    Trace::make_trace_fn(TRACE_ACTIVE, LIB_NAME, &"place_robot_at")(&format!("Robot removed from location ({},{})", &x, &y));
-    self.locations[index_from_x_y(&self.width, &x, &y)].id = -1;
+    Trace::make_trace_fn(TRACE_ACTIVE, LIB_NAME, "place_robot_at")(&format!("Robot removed from location ({},{})", &x, &y));
+    self.locations[index_from_x_y(&self.width, x, y)].vacate();
   }
 
   // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
   // Should I go that way?
   // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
   pub fn is_it_safe(&self, x : &i32, y : &i32, heading : &Heading) -> bool {
-    let loc = &self.locations[index_from_x_y(&self.width, &x, &y)];
+    self.rules.scents == ScentMode::Off ||
+    !self.locations[index_from_x_y(&self.width, x, y)].is_scented(heading)
+  }
 
-    match heading {
-      Heading::North => loc.can_go_north,
-      Heading::East  => loc.can_go_east,
-      Heading::South => loc.can_go_south,
-      Heading::West  => loc.can_go_west
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  // What's over there?
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  pub fn look(&self, x : &i32, y : &i32, heading : &Heading) -> Outlook {
+    let (dx, dy)       = heading.offset();
+    let (new_x, new_y) = (x + dx, y + dy);
+
+    if !self.is_it_safe(x, y, heading) {
+      Outlook::Scent
+    }
+    else {
+      self.occupant(&new_x, &new_y)
+    }
+  }
+
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  // Coordinates as seen from outside
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  pub fn to_external(&self, x : &i32, y : &i32) -> (i32, i32) {
+    self.coords.external(*x, *y, self.height)
+  }
+
+  pub fn to_internal(&self, x : &i32, y : &i32) -> (i32, i32) {
+    self.coords.internal(*x, *y, self.height)
+  }
+
+  pub fn contains(&self, x : &i32, y : &i32) -> bool {
+    (0..self.width).contains(x) && (0..self.height).contains(y)
+  }
+
+  // Who (if anyone) is at this location?  Locations outside the world are reported as the edge
+  pub fn occupant(&self, x : &i32, y : &i32) -> Outlook {
+    if !self.contains(x, y) {
+      Outlook::Edge
+    }
+    else {
+      let loc = &self.locations[index_from_x_y(&self.width, x, y)];
+
+      match loc.occupant() {
+        _ if loc.is_obstacle() => Outlook::Obstacle
+      , None                   => Outlook::Free
+      , Some(id)               => Outlook::Occupied(id)
+      }
     }
   }
 
@@ -100,66 +246,306 @@ impl World {
   // Going that way was a bad idea...
   // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
   pub fn here_be_monsters(&mut self, x : &i32, y : &i32, heading : &Heading) {
-    let loc = &mut self.locations[index_from_x_y(&self.width, &x, &y)];
+    if self.rules.scents == ScentMode::Off {
+      return
+    }
+
+    self.locations[index_from_x_y(&self.width, x, y)].add_scent(heading);
+  }
+
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  // Where have robots been lost so far?
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  pub fn scents(&self) -> Vec<(i32, i32, Heading)> {
+    let mut scents = vec!();
 
-    match heading {
-      Heading::North => loc.can_go_north = false,
-      Heading::East  => loc.can_go_east  = false,
-      Heading::South => loc.can_go_south = false,
-      Heading::West  => loc.can_go_west  = false
+    for (idx, loc) in self.locations.iter().enumerate() {
+      let (x, y) = x_y_from_index(&self.width, idx);
+
+      for heading in [Heading::North, Heading::East, Heading::South, Heading::West] {
+        if loc.is_scented(&heading) {
+          scents.push((x, y, heading));
+        }
+      }
+    }
+
+    scents
+  }
+
+  pub fn clear_scents(&mut self) {
+    for loc in &mut self.locations {
+      loc.clear_scents();
     }
   }
 
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  // Obstacles
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  pub fn add_obstacle(&mut self, x : &i32, y : &i32) {
+    self.locations[index_from_x_y(&self.width, x, y)].block();
+  }
+
+  pub fn is_obstacle(&self, x : &i32, y : &i32) -> bool {
+    self.contains(x, y) && self.locations[index_from_x_y(&self.width, x, y)].is_obstacle()
+  }
+
+  pub fn obstacles(&self) -> Vec<(i32, i32)> {
+    self.locations.iter()
+      .enumerate()
+      .filter(|(_, loc)| loc.is_obstacle())
+      .map(|(idx, _)| x_y_from_index(&self.width, idx))
+      .collect()
+  }
+
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  // Charging stations
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  pub fn add_charger(&mut self, x : &i32, y : &i32, rate : u32) {
+    self.chargers.insert(index_from_x_y(&self.width, x, y), rate);
+  }
+
+  // How much energy per tick does the charging station at this location give (if there is one)?
+  pub fn charger_at(&self, x : &i32, y : &i32) -> Option<u32> {
+    if !self.contains(x, y) {
+      None
+    }
+    else {
+      self.chargers.get(&index_from_x_y(&self.width, x, y)).copied()
+    }
+  }
+
+  pub fn chargers(&self) -> Vec<(i32, i32, u32)> {
+    self.chargers.iter()
+      .map(|(idx, rate)| {
+        let (x, y) = x_y_from_index(&self.width, *idx);
+        (x, y, *rate)
+      })
+      .collect()
+  }
+
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  // Items
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  pub fn put_item(&mut self, x : &i32, y : &i32, item : &str) {
+    self.items.entry(index_from_x_y(&self.width, x, y)).or_default().push(item.to_string());
+  }
+
+  // Remove the item on top of the pile at this location
+  pub fn take_item(&mut self, x : &i32, y : &i32) -> Option<String> {
+    let idx  = index_from_x_y(&self.width, x, y);
+    let pile = self.items.get_mut(&idx)?;
+    let item = pile.pop();
+
+    if pile.is_empty() {
+      self.items.remove(&idx);
+    }
+
+    item
+  }
+
+  pub fn items_at(&self, x : &i32, y : &i32) -> &[String] {
+    self.items.get(&index_from_x_y(&self.width, x, y)).map_or(&[], |pile| pile.as_slice())
+  }
+
+  pub fn items(&self) -> Vec<(i32, i32, String)> {
+    self.items.iter()
+      .flat_map(|(idx, pile)| {
+        let (x, y) = x_y_from_index(&self.width, *idx);
+        pile.iter().map(move |item| (x, y, item.clone()))
+      })
+      .collect()
+  }
+
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  // The same world, but with all robots removed, leaving behind only the scents, obstacles, charging stations and
+  // items lying on the ground
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  pub fn without_robots(&self) -> World {
+    let mut world = World::new(&self.width, &self.height);
+    world.costs  = self.costs;
+    world.coords = self.coords;
+    world.rules  = self.rules;
+
+    for (x, y, heading) in self.scents() {
+      world.here_be_monsters(&x, &y, &heading);
+    }
+
+    for (x, y) in self.obstacles() {
+      world.add_obstacle(&x, &y);
+    }
+
+    for (x, y, rate) in self.chargers() {
+      world.add_charger(&x, &y, rate);
+    }
+
+    for (x, y, item) in self.items() {
+      world.put_item(&x, &y, &item);
+    }
+
+    world
+  }
+
   // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
   // Constructor
   // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
   pub fn new(width : &i32, height : &i32) -> World {
-    return World {
+    World {
       height    : *height
     , width     : *width
-    , locations : create_world_locations(&width, &height)
+    , locations : create_world_locations(width, height)
+    , roster    : BTreeMap::new()
+    , costs     : EnergyCosts::default()
+    , coords    : Coords::default()
+    , rules     : Rules::default()
+    , chargers  : BTreeMap::new()
+    , items     : BTreeMap::new()
     }
   }
 }
 
 // *********************************************************************************************************************
-// Create a new world from stdin data
+// World dimensions definition
 // *********************************************************************************************************************
-pub fn create_world() -> Result<World, &'static str> {
-  // Keep reading stdin until we get some valid world dimensions or hit EOF
-  prompt(PROMPT_NEW_WORLD);
-
-  for stdin_data in BufReader::new(std::io::stdin()).lines() {
-    match &stdin_data.unwrap().trim().parse::<Dimensions>() {
-      Ok(dims)     => return Ok(World::new(&dims.width, &dims.height))
-    , Err(err_msg) => {
-        eprintln!("Error: {}", err_msg);
-        prompt(PROMPT_NEW_WORLD);
-      }
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Dimensions {
+  pub width  : i32
+, pub height : i32
+}
+
+// *********************************************************************************************************************
+// World dimensions implementation
+// *********************************************************************************************************************
+impl str::FromStr for Dimensions {
+  type Err = DimensionsError;
+
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  // Parse line from stdin that we expect to contain world dimensions: width then height, within the default limits
+  fn from_str(s: &str) -> Result<Dimensions, Self::Err> {
+    Dialect::Native.parse_dimensions(s, &Limits::DEFAULT)
+  }
+}
+
+// *********************************************************************************************************************
+// Why some world dimensions were refused
+// *********************************************************************************************************************
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum DimensionsError {
+  Unreadable(&'static str)        // Not a pair of integers
+, OutOfRange(Limits)              // A width or height outside these limits
+, UpperRightOutOfRange(Limits)    // An upper-right coordinate outside these limits, in the classic dialect
+}
+
+impl fmt::Display for DimensionsError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      DimensionsError::Unreadable(msg) => write!(f, "{}", msg)
+    , DimensionsError::OutOfRange(limits) if limits.max_width == limits.max_height =>
+        write!(f, "Both world dimensions must be in the range 1 to {}", limits.max_width)
+    , DimensionsError::OutOfRange(limits) =>
+        write!(f, "World width must be in the range 1 to {} and height in the range 1 to {}", limits.max_width, limits.max_height)
+    , DimensionsError::UpperRightOutOfRange(limits) if limits.max_width == limits.max_height =>
+        write!(f, "Both upper-right coordinates must be in the range 0 to {}", limits.max_width)
+    , DimensionsError::UpperRightOutOfRange(limits) =>
+        write!(f, "Upper-right X must be in the range 0 to {} and Y in the range 0 to {}", limits.max_width, limits.max_height)
     }
   }
+}
 
-  Err(EOF_ENCOUNTERED)
+// *********************************************************************************************************************
+// World limits definition
+// *********************************************************************************************************************
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Limits {
+  pub max_width  : i32
+, pub max_height : i32
 }
 
 // *********************************************************************************************************************
-// World dimensions definition
+// World limits implementation
 // *********************************************************************************************************************
-#[derive(Debug)]
-pub struct Dimensions {
-  pub width  : i32
-, pub height : i32
+impl Limits {
+  pub const DEFAULT : Limits = Limits { max_width : WORLD_MAX_WIDTH, max_height : WORLD_MAX_HEIGHT };
+
+  pub fn new(max_width : i32, max_height : i32) -> Result<Limits, &'static str> {
+    if (WORLD_MIN_WIDTH  ..= WORLD_LIMIT_CEILING).contains(&max_width) &&
+       (WORLD_MIN_HEIGHT ..= WORLD_LIMIT_CEILING).contains(&max_height) {
+      Ok(Limits { max_width, max_height })
+    }
+    else {
+      Err(PARSE_ERROR_BAD_LIMITS)
+    }
+  }
+
+  // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+  // Check that the dimensions are within these limits
+  pub fn dimensions(&self, width : i32, height : i32) -> Result<Dimensions, DimensionsError> {
+    if (WORLD_MIN_WIDTH  ..= self.max_width).contains(&width) &&
+       (WORLD_MIN_HEIGHT ..= self.max_height).contains(&height) {
+      Ok(Dimensions{ width, height })
+    }
+    else {
+      Err(DimensionsError::OutOfRange(*self))
+    }
+  }
 }
 
 // *********************************************************************************************************************
-// World dimensions implementation
+// Input dialect definition
 // *********************************************************************************************************************
-impl str::FromStr for Dimensions {
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum Dialect {
+  #[default]
+  Native      // The first line gives the width and height of the world
+, Classic     // The first line gives the coordinates of the upper-right location
+}
+
+// *********************************************************************************************************************
+// Input dialect implementation
+// *********************************************************************************************************************
+impl fmt::Display for Dialect {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Dialect::Native  => write!(f, "native")
+    , Dialect::Classic => write!(f, "classic")
+    }
+  }
+}
+
+impl str::FromStr for Dialect {
   type Err = &'static str;
 
+  fn from_str(s: &str) -> Result<Dialect, Self::Err> {
+    match s.trim().to_ascii_lowercase().as_str() {
+      "native"  => Ok(Dialect::Native)
+    , "classic" => Ok(Dialect::Classic)
+    , _         => Err(PARSE_ERROR_BAD_DIALECT)
+    }
+  }
+}
+
+impl Dialect {
   // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
-  // Parse line from stdin that we expect to contain world dimensions: width then height
-  fn from_str(s: &str) -> Result<Dimensions, Self::Err> {
+  // The classic Martian Robots input gives the coordinates of the upper-right location instead, so "5 3" describes a
+  // world 6 wide and 4 high.  The classic limit applies to the coordinates, so such a world can be one wider and one
+  // higher than a native one
+  pub fn parse_dimensions(&self, s : &str, limits : &Limits) -> Result<Dimensions, DimensionsError> {
+    let (a, b) = parse_pair(s).map_err(DimensionsError::Unreadable)?;
+
+    match self {
+      Dialect::Native  => limits.dimensions(a, b)
+    , Dialect::Classic if (0 ..= limits.max_width).contains(&a) && (0 ..= limits.max_height).contains(&b) =>
+        Ok(Dimensions { width : a + 1, height : b + 1 })
+    , Dialect::Classic => Err(DimensionsError::UpperRightOutOfRange(*limits))
+    }
+  }
+}
+
+// *********************************************************************************************************************
+// Private API
+// *********************************************************************************************************************
+
+// Two integers: either a width and height, or the coordinates of the upper-right location
+fn parse_pair(s : &str) -> Result<(i32, i32), &'static str> {
     let mut line_iter = s.split_ascii_whitespace();
 
     let w = match line_iter.next() {
@@ -180,47 +566,29 @@ impl str::FromStr for Dimensions {
     , None => return Err(PARSE_ERROR_MISSING_DIM)
     };
 
-    // Check that the parsed dimensions are within the permitted range
-    if w >= WORLD_MIN_WIDTH  && w <= WORLD_MAX_WIDTH &&
-       h >= WORLD_MIN_HEIGHT && h <= WORLD_MAX_HEIGHT { 
-        Ok(Dimensions{ width : w, height : h })
-    }
-    else {
-      Err(ERROR_INVALID_WORLD_DIMS)
-    }
-  }
+    Ok((w, h))
 }
 
-// *********************************************************************************************************************
-// Private API
-// *********************************************************************************************************************
 fn index_from_x_y(width : &i32, x : &i32, y : &i32) -> usize {
   (y * width + x) as usize  
 }
 
-fn create_world_locations(width : &i32, height : &i32) -> Vec<Location> {
-  let mut w : Vec<Location> = vec!();
-
-  for i in 0..*height {
-    for j in 0..*width {
-      w.push(Location::new(j, i));
-    }
-  }
-
-  w
+fn x_y_from_index(width : &i32, idx : usize) -> (i32, i32) {
+  (idx as i32 % width, idx as i32 / width)
 }
 
-fn prompt(prompt_msg : &str) {
-  print!("{} : ", prompt_msg);
-  let _ = std::io::stdout().flush();
+fn create_world_locations(width : &i32, height : &i32) -> Vec<Location> {
+  vec!(Location::new(); (width * height) as usize)
 }
 
-fn write_horiz_line(fmt: &mut fmt::Formatter, width : &i32) {
-  for _ in 0..(*width * 4) {
+fn write_horiz_line(fmt: &mut fmt::Formatter, margin : &str, width : &i32, cell_width : usize) {
+  let _ = write!(fmt, "{}", margin);
+
+  for _ in 0..(*width * (cell_width as i32 + 3)) {
     let _ = write!(fmt, "{}", FORMAT_CHAR_HORIZ);
   }
 
-  let _ = write!(fmt, "{}\n", FORMAT_CHAR_HORIZ);
+  let _ = writeln!(fmt, "{}", FORMAT_CHAR_HORIZ);
 }
 
 // *********************************************************************************************************************
@@ -254,39 +622,143 @@ mod tests {
   fn parse_test_invalid_dims() {
     // Both dimensions missing
     let d = "".parse::<Dimensions>();
-    assert_eq!(d.err(), Some(PARSE_ERROR_MISSING_DIMS));
+    assert_eq!(d.err(), Some(DimensionsError::Unreadable(PARSE_ERROR_MISSING_DIMS)));
 
     // One valid dimension, but should be two
     let d = "1".parse::<Dimensions>();
-    assert_eq!(d.err(), Some(PARSE_ERROR_MISSING_DIM));
+    assert_eq!(d.err(), Some(DimensionsError::Unreadable(PARSE_ERROR_MISSING_DIM)));
 
     // One invalid dimension
     let d = "a".parse::<Dimensions>();
-    assert_eq!(d.err(), Some(PARSE_ERROR_BAD_WIDTH));
+    assert_eq!(d.err(), Some(DimensionsError::Unreadable(PARSE_ERROR_BAD_WIDTH)));
 
     // Two dimensions, but the first one is invalid
     let d = "a 1".parse::<Dimensions>();
-    assert_eq!(d.err(), Some(PARSE_ERROR_BAD_WIDTH));
+    assert_eq!(d.err(), Some(DimensionsError::Unreadable(PARSE_ERROR_BAD_WIDTH)));
 
     // Two dimensions, but the second one is invalid
     let d = "1 b".parse::<Dimensions>();
-    assert_eq!(d.err(), Some(PARSE_ERROR_BAD_HEIGHT));
+    assert_eq!(d.err(), Some(DimensionsError::Unreadable(PARSE_ERROR_BAD_HEIGHT)));
     
     // Both dimensions parse correctly but at least one is invalid
     let d = "0 0".parse::<Dimensions>();
-    assert_eq!(d.err(), Some(ERROR_INVALID_WORLD_DIMS));
+    assert_eq!(d.err(), Some(DimensionsError::OutOfRange(Limits::DEFAULT)));
 
     // Both dimensions parse correctly but at least one is invalid
     let d = "-1 -1".parse::<Dimensions>();
-    assert_eq!(d.err(), Some(ERROR_INVALID_WORLD_DIMS));
+    assert_eq!(d.err(), Some(DimensionsError::OutOfRange(Limits::DEFAULT)));
 
     // Both dimensions parse correctly but at least one is invalid
     let d = "25 51".parse::<Dimensions>();
-    assert_eq!(d.err(), Some(ERROR_INVALID_WORLD_DIMS));
+    assert_eq!(d.err(), Some(DimensionsError::OutOfRange(Limits::DEFAULT)));
 
     // Both dimensions parse correctly but at least one is invalid
     let d = "52 50".parse::<Dimensions>();
-    assert_eq!(d.err(), Some(ERROR_INVALID_WORLD_DIMS));
+    assert_eq!(d.err(), Some(DimensionsError::OutOfRange(Limits::DEFAULT)));
+  }
+
+  #[test]
+  fn parse_test_classic_dims() {
+    let d = Dialect::Classic.parse_dimensions("5 3", &Limits::DEFAULT).unwrap();
+    assert_eq!((d.width, d.height), (6, 4));
+
+    let d = "Classic".parse::<Dialect>().unwrap().parse_dimensions("0 0", &Limits::DEFAULT).unwrap();
+    assert_eq!((d.width, d.height), (1, 1));
+
+    let d = Dialect::Classic.parse_dimensions("50 50", &Limits::DEFAULT).unwrap();
+    assert_eq!((d.width, d.height), (51, 51));
+
+    let out_of_range = Some(DimensionsError::UpperRightOutOfRange(Limits::DEFAULT));
+    assert_eq!(Dialect::Classic.parse_dimensions("51 3", &Limits::DEFAULT).err(),         out_of_range);
+    assert_eq!(Dialect::Classic.parse_dimensions("2147483647 3", &Limits::DEFAULT).err(), out_of_range);
+    assert_eq!(Dialect::Classic.parse_dimensions("5", &Limits::DEFAULT).err(), Some(DimensionsError::Unreadable(PARSE_ERROR_MISSING_DIM)));
+    assert_eq!("martian".parse::<Dialect>(),                    Err(PARSE_ERROR_BAD_DIALECT));
+  }
+
+  #[test]
+  fn world_limits() {
+    let limits = Limits::new(80, 20).unwrap();
+    assert_eq!(limits.dimensions(80, 20), Ok(Dimensions { width : 80, height : 20 }));
+    assert_eq!( limits.dimensions(81, 20).err().unwrap().to_string()
+              , "World width must be in the range 1 to 80 and height in the range 1 to 20"
+              );
+    assert_eq!( Dialect::Classic.parse_dimensions("80 21", &limits).err().unwrap().to_string()
+              , "Upper-right X must be in the range 0 to 80 and Y in the range 0 to 20"
+              );
+
+    let limits = Limits::new(100, 100).unwrap();
+    assert_eq!(limits.dimensions(0, 100).err().unwrap().to_string(), "Both world dimensions must be in the range 1 to 100");
+    assert_eq!(Dialect::Classic.parse_dimensions("100 100", &limits), Ok(Dimensions { width : 101, height : 101 }));
+    assert_eq!(DimensionsError::OutOfRange(Limits::DEFAULT).to_string(), "Both world dimensions must be in the range 1 to 50");
+
+    assert_eq!(Limits::new(WORLD_MAX_WIDTH, WORLD_MAX_HEIGHT), Ok(Limits::DEFAULT));
+    assert_eq!(Limits::new(0, 50),                          Err(PARSE_ERROR_BAD_LIMITS));
+    assert_eq!(Limits::new(50, WORLD_LIMIT_CEILING + 1),    Err(PARSE_ERROR_BAD_LIMITS));
+  }
+
+  #[test]
+  fn look_around() {
+    let mut world = World::new(&3, &3);
+
+    world.place_robot_at(&4, &1, &2);
+    world.here_be_monsters(&0, &0, &Heading::West);
+    world.add_obstacle(&0, &1);
+
+    assert_eq!(world.look(&1, &1, &Heading::North), Outlook::Occupied(4));
+    assert_eq!(world.look(&1, &1, &Heading::East),  Outlook::Free);
+    assert_eq!(world.look(&1, &1, &Heading::West),  Outlook::Obstacle);
+    assert_eq!(world.look(&0, &0, &Heading::South), Outlook::Edge);
+    assert_eq!(world.look(&0, &0, &Heading::West),  Outlook::Scent);
+    assert_eq!(world.without_robots().obstacles(),  vec!((0, 1)));
+  }
+
+  #[test]
+  fn render_robot_names() {
+    let mut world = World::new(&3, &1);
+
+    world.enrol_robot(&0, &None);
+    world.place_robot_at(&0, &0, &0);
+    assert_eq!(world.to_string(), "\n-------------\n| 0 |   |   |\n-------------\n");
+
+    world.enrol_robot(&1, &Some(String::from("scout")));
+    world.place_robot_at(&1, &2, &0);
+    assert_eq!(world.next_robot_id(), Some(2));
+    assert_eq!( world.to_string()
+              , "\n-------------------------\n|   0   |       | scout |\n-------------------------\n"
+              );
+  }
+
+  #[test]
+  fn render_coordinates() {
+    let mut world = World::new(&2, &3);
+
+    world.coords = "1 top-left".parse().unwrap();
+    world.enrol_robot(&0, &None);
+    world.place_robot_at(&0, &0, &0);
+
+    assert_eq!(world.to_external(&0, &0), (1, 3));
+    assert_eq!(world.to_internal(&2, &1), (1, 2));
+    assert_eq!(world.to_string(), "\n  ---------\n1 |   |   |\n2 |   |   |\n3 | 0 |   |\n  ---------\n    1   2\n");
+  }
+
+  #[test]
+  fn render_chargers_and_items() {
+    let mut world = World::new(&3, &1);
+
+    world.add_charger(&1, &0, 2);
+    world.here_be_monsters(&0, &0, &Heading::West);
+    assert_eq!(world.to_string(), "\n-------------\n|   | + |   |\n-------------\n");
+
+    world.put_item(&2, &0, "crate");
+    assert_eq!(world.to_string(), "\n-------------\n|   | + | * |\n-------------\n");
+    assert_eq!(world.charger_at(&1, &0), Some(2));
+    assert_eq!(world.charger_at(&3, &0), None);
+
+    // Chargers survive a reset, even when the scents are cleared
+    let mut world = world.without_robots();
+    world.clear_scents();
+    assert_eq!((world.chargers(), world.scents()), (vec!((1, 0, 2)), vec!()));
+    assert_eq!(world.items_at(&2, &0), &[String::from("crate")]);
   }
 
   #[test]